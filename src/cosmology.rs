@@ -0,0 +1,296 @@
+//! Expansion histories of the early universe.
+//!
+//! A `Cosmology` supplies the Hubble rate, the entropy density of the thermal
+//! bath and the rate at which the bath cools. All implementations here assume
+//! that the entropy of the Standard Model bath is conserved; the extra energy
+//! component of the non-standard histories only modifies the Hubble rate.
+//! The degrees of freedom of the bath are taken from a `DegreesOfFreedom`,
+//! which defaults to `HaliaxDof`.
+
+use crate::dof::{DegreesOfFreedom, HaliaxDof};
+use cyphus_integration::prelude::*;
+use haliax_constants::prelude::*;
+use haliax_thermal_functions::prelude::*;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+pub trait Cosmology {
+    /// Compute the Hubble rate at the temperature `temp`.
+    fn hubble_rate(&self, temp: f64) -> f64;
+    /// Compute the entropy density of the thermal bath at `temp`.
+    fn entropy_density(&self, temp: f64) -> f64;
+    /// Compute the energy density of the thermal bath at `temp`. Defaults to
+    /// `3/4 T s`, i.e. `g_eff = h_eff`.
+    fn bath_energy_density(&self, temp: f64) -> f64 {
+        0.75 * temp * self.entropy_density(temp)
+    }
+    /// Compute the time derivative of the temperature of the bath at `temp`.
+    fn dtemp_dt(&self, temp: f64) -> f64;
+    /// Compute `d log(x) / dt = -d log(T) / dt`.
+    fn dlogx_dt(&self, temp: f64) -> f64 {
+        -self.dtemp_dt(temp) / temp
+    }
+    /// Get the same expansion history with the degrees of freedom of the
+    /// bath replaced by `dof`.
+    fn with_dof(
+        &self,
+        dof: Arc<dyn DegreesOfFreedom + Send + Sync>,
+    ) -> Arc<dyn Cosmology + Send + Sync>;
+}
+
+/// Effective number of entropic degrees of freedom of the Standard Model.
+pub fn sm_heff(temp: f64) -> f64 {
+    45.0 * sm_entropy_density(temp) / (2.0 * PI * PI * temp.powi(3))
+}
+
+/// Energy density of the Standard Model bath. The energetic degrees of
+/// freedom are approximated by the entropic ones, which differ only below
+/// electron-positron annihilation.
+pub fn sm_energy_density(temp: f64) -> f64 {
+    PI * PI / 30.0 * sm_heff(temp) * temp.powi(4)
+}
+
+/// Hubble rate of a universe with total energy density `rho`. `M_PLANK` is
+/// the Planck mass, not the reduced one, as in the Boltzmann equation.
+pub(crate) fn hubble_rate_from_energy_density(rho: f64) -> f64 {
+    (8.0 * PI * rho / 3.0).sqrt() / M_PLANK
+}
+
+/// Hubble rate of a radiation-dominated universe with the energy density
+/// `rho` added to that of the bath.
+fn hubble_rate_with(dof: &dyn DegreesOfFreedom, temp: f64, rho: f64) -> f64 {
+    hubble_rate_from_energy_density(dof.energy_density(temp) + rho)
+}
+
+/// Standard radiation-dominated expansion history.
+#[derive(Clone)]
+pub struct RadiationDomination {
+    /// Degrees of freedom of the thermal bath.
+    pub dof: Arc<dyn DegreesOfFreedom + Send + Sync>,
+}
+
+impl RadiationDomination {
+    pub fn new(dof: Arc<dyn DegreesOfFreedom + Send + Sync>) -> RadiationDomination {
+        RadiationDomination { dof }
+    }
+}
+
+impl Default for RadiationDomination {
+    fn default() -> RadiationDomination {
+        RadiationDomination::new(Arc::new(HaliaxDof))
+    }
+}
+
+impl Cosmology for RadiationDomination {
+    fn hubble_rate(&self, temp: f64) -> f64 {
+        hubble_rate_with(self.dof.as_ref(), temp, 0.0)
+    }
+    fn entropy_density(&self, temp: f64) -> f64 {
+        self.dof.entropy_density(temp)
+    }
+    fn bath_energy_density(&self, temp: f64) -> f64 {
+        self.dof.energy_density(temp)
+    }
+    fn dtemp_dt(&self, temp: f64) -> f64 {
+        // Includes the change in the entropic degrees of freedom through
+        // `sqrt(g*) = h / sqrt(g) (1 + T h' / 3h)`.
+        -self.dof.entropy_density(temp) / ((PI / 45.0).sqrt() * M_PLANK * self.dof.sqrt_gstar(temp))
+    }
+    fn with_dof(
+        &self,
+        dof: Arc<dyn DegreesOfFreedom + Send + Sync>,
+    ) -> Arc<dyn Cosmology + Send + Sync> {
+        Arc::new(RadiationDomination::new(dof))
+    }
+}
+
+/// Compute `dT/dt` for an entropy-conserving cosmology with Hubble rate
+/// `hubble` by rescaling the radiation-dominated result.
+fn entropy_conserving_dtemp_dt(
+    dof: &Arc<dyn DegreesOfFreedom + Send + Sync>,
+    temp: f64,
+    hubble: f64,
+) -> f64 {
+    let rd = RadiationDomination::new(dof.clone());
+    rd.dtemp_dt(temp) * hubble / rd.hubble_rate(temp)
+}
+
+/// Compute the ratio of the energy density of a component with equation of
+/// state `w` to that of the bath, given that the two are equal at `t_r` and
+/// that the bath entropy is conserved.
+fn constant_w_ratio(dof: &dyn DegreesOfFreedom, temp: f64, w: f64, t_r: f64) -> f64 {
+    (dof.heff(temp) / dof.heff(t_r)).powf(w) * (temp / t_r).powf(3.0 * w - 1.0)
+}
+
+/// Early period of matter domination. A pressureless component equals the
+/// radiation energy density at `t_eq` and dominates below it, until it is
+/// removed at `t_end`. The entropy released when the component decays is not
+/// included; see `KineticMixing::relic_density_reheating` for that case.
+#[derive(Clone)]
+pub struct EarlyMatterDomination {
+    /// Temperature at which matter and radiation have equal energy densities.
+    pub t_eq: f64,
+    /// Temperature at which the matter component disappears.
+    pub t_end: f64,
+    /// Degrees of freedom of the thermal bath.
+    pub dof: Arc<dyn DegreesOfFreedom + Send + Sync>,
+}
+
+impl EarlyMatterDomination {
+    pub fn new(t_eq: f64, t_end: f64) -> EarlyMatterDomination {
+        EarlyMatterDomination {
+            t_eq,
+            t_end,
+            dof: Arc::new(HaliaxDof),
+        }
+    }
+}
+
+impl Cosmology for EarlyMatterDomination {
+    fn hubble_rate(&self, temp: f64) -> f64 {
+        let dof = self.dof.as_ref();
+        let rho = if temp > self.t_end {
+            constant_w_ratio(dof, temp, 0.0, self.t_eq) * dof.energy_density(temp)
+        } else {
+            0.0
+        };
+        hubble_rate_with(dof, temp, rho)
+    }
+    fn entropy_density(&self, temp: f64) -> f64 {
+        self.dof.entropy_density(temp)
+    }
+    fn bath_energy_density(&self, temp: f64) -> f64 {
+        self.dof.energy_density(temp)
+    }
+    fn dtemp_dt(&self, temp: f64) -> f64 {
+        entropy_conserving_dtemp_dt(&self.dof, temp, self.hubble_rate(temp))
+    }
+    fn with_dof(
+        &self,
+        dof: Arc<dyn DegreesOfFreedom + Send + Sync>,
+    ) -> Arc<dyn Cosmology + Send + Sync> {
+        Arc::new(Self {
+            dof,
+            ..self.clone()
+        })
+    }
+}
+
+/// Kination: a component with `w = 1` that equals the radiation energy
+/// density at `t_r` and dominates above it.
+#[derive(Clone)]
+pub struct Kination {
+    /// Temperature at which kination and radiation have equal energy densities.
+    pub t_r: f64,
+    /// Degrees of freedom of the thermal bath.
+    pub dof: Arc<dyn DegreesOfFreedom + Send + Sync>,
+}
+
+impl Kination {
+    pub fn new(t_r: f64) -> Kination {
+        Kination {
+            t_r,
+            dof: Arc::new(HaliaxDof),
+        }
+    }
+}
+
+impl Cosmology for Kination {
+    fn hubble_rate(&self, temp: f64) -> f64 {
+        let dof = self.dof.as_ref();
+        let rho = constant_w_ratio(dof, temp, 1.0, self.t_r) * dof.energy_density(temp);
+        hubble_rate_with(dof, temp, rho)
+    }
+    fn entropy_density(&self, temp: f64) -> f64 {
+        self.dof.entropy_density(temp)
+    }
+    fn bath_energy_density(&self, temp: f64) -> f64 {
+        self.dof.energy_density(temp)
+    }
+    fn dtemp_dt(&self, temp: f64) -> f64 {
+        entropy_conserving_dtemp_dt(&self.dof, temp, self.hubble_rate(temp))
+    }
+    fn with_dof(
+        &self,
+        dof: Arc<dyn DegreesOfFreedom + Send + Sync>,
+    ) -> Arc<dyn Cosmology + Send + Sync> {
+        Arc::new(Self {
+            dof,
+            ..self.clone()
+        })
+    }
+}
+
+/// A component with a temperature-dependent equation of state `w(T)` which
+/// equals the radiation energy density at `t_r`.
+#[derive(Clone)]
+pub struct EquationOfState {
+    /// Equation of state parameter as a function of the bath temperature.
+    pub w: Arc<dyn Fn(f64) -> f64 + Send + Sync>,
+    /// Temperature at which the component and radiation have equal energy densities.
+    pub t_r: f64,
+    /// Degrees of freedom of the thermal bath.
+    pub dof: Arc<dyn DegreesOfFreedom + Send + Sync>,
+    gk: GaussKronrodIntegrator,
+}
+
+impl EquationOfState {
+    pub fn new<F>(w: F, t_r: f64) -> EquationOfState
+    where
+        F: Fn(f64) -> f64 + Send + Sync + 'static,
+    {
+        let gk = GaussKronrodIntegratorBuilder::default()
+            .epsabs(1e-10)
+            .epsrel(1e-8)
+            .limit(100)
+            .build();
+        EquationOfState {
+            w: Arc::new(w),
+            t_r,
+            dof: Arc::new(HaliaxDof),
+            gk,
+        }
+    }
+    /// Compute the ratio of the energy density of the component to that of
+    /// the bath at `temp`.
+    pub fn energy_density_ratio(&self, temp: f64) -> f64 {
+        // With a ~ h^(-1/3) / T, rho ~ exp(-3 int (1 + w) dlog(a)) gives
+        // log(rho / rho_r) = int [3 (1 + w) + (1 + w) dlog(h)/dlog(T) - 4 - dlog(h)/dlog(T)] dlog(T).
+        let integrand = |logt: f64| -> f64 {
+            let t = logt.exp();
+            let dlogh = self.dof.dlogheff_dlogt(t);
+            let w = (self.w)(t);
+            3.0 * w - 1.0 + w * dlogh
+        };
+        self.gk
+            .integrate(integrand, self.t_r.ln(), temp.ln())
+            .val
+            .exp()
+    }
+}
+
+impl Cosmology for EquationOfState {
+    fn hubble_rate(&self, temp: f64) -> f64 {
+        let dof = self.dof.as_ref();
+        let rho = self.energy_density_ratio(temp) * dof.energy_density(temp);
+        hubble_rate_with(dof, temp, rho)
+    }
+    fn entropy_density(&self, temp: f64) -> f64 {
+        self.dof.entropy_density(temp)
+    }
+    fn bath_energy_density(&self, temp: f64) -> f64 {
+        self.dof.energy_density(temp)
+    }
+    fn dtemp_dt(&self, temp: f64) -> f64 {
+        entropy_conserving_dtemp_dt(&self.dof, temp, self.hubble_rate(temp))
+    }
+    fn with_dof(
+        &self,
+        dof: Arc<dyn DegreesOfFreedom + Send + Sync>,
+    ) -> Arc<dyn Cosmology + Send + Sync> {
+        Arc::new(Self {
+            dof,
+            ..self.clone()
+        })
+    }
+}
@@ -0,0 +1,629 @@
+//! Effective numbers of relativistic degrees of freedom of the thermal bath.
+//!
+//! A `DegreesOfFreedom` supplies the energetic and entropic degrees of
+//! freedom, `g_eff` and `h_eff`, from which the energy density, the entropy
+//! density and `sqrt(g_*)` of the bath follow. `HaliaxDof` wraps the tables
+//! of `haliax-thermal-functions`. `TabulatedDof` interpolates any table, for
+//! example one taken from the literature, and `DofBand` pairs a table with
+//! the edges of its uncertainty band. `DofTable` lists the embedded tables:
+//! a fit to lattice QCD, with a systematic band, and two toy models with a
+//! sharp transition.
+//! The uncertainty of the QCD equation of state matters when freeze-out
+//! happens at `T ~ 0.1 - 1 GeV`. `ExtendedDof` adds species in equilibrium
+//! with the bath to any of these.
+
+use crate::cosmology::sm_heff;
+use crate::error::{check_parameter, ModelError};
+use crate::reaction_network::Species;
+use crate::statistics::{
+    equilibrium_energy_density, equilibrium_heat_capacity, equilibrium_pressure, Statistics,
+};
+use haliax_constants::prelude::*;
+use haliax_thermal_functions::prelude::*;
+use lazy_static::lazy_static;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+pub trait DegreesOfFreedom {
+    /// Compute the effective number of energetic degrees of freedom at `temp`.
+    fn geff(&self, temp: f64) -> f64;
+    /// Compute the effective number of entropic degrees of freedom at `temp`.
+    fn heff(&self, temp: f64) -> f64;
+    /// Compute `d log(h_eff) / d log(T)`.
+    fn dlogheff_dlogt(&self, temp: f64) -> f64 {
+        let h = 1e-3;
+        ((self.heff(temp * (1.0 + h))).ln() - (self.heff(temp * (1.0 - h))).ln())
+            / ((1.0 + h) / (1.0 - h)).ln()
+    }
+    /// Compute `sqrt(g_*) = h_eff / sqrt(g_eff) (1 + T h_eff' / (3 h_eff))`.
+    fn sqrt_gstar(&self, temp: f64) -> f64 {
+        self.heff(temp) / self.geff(temp).sqrt() * (1.0 + self.dlogheff_dlogt(temp) / 3.0)
+    }
+    /// Compute the energy density of the bath at `temp`.
+    fn energy_density(&self, temp: f64) -> f64 {
+        PI * PI / 30.0 * self.geff(temp) * temp.powi(4)
+    }
+    /// Compute the entropy density of the bath at `temp`.
+    fn entropy_density(&self, temp: f64) -> f64 {
+        2.0 * PI * PI / 45.0 * self.heff(temp) * temp.powi(3)
+    }
+}
+
+/// Standard Model degrees of freedom of `haliax-thermal-functions`. The
+/// energetic degrees of freedom are approximated by the entropic ones, which
+/// differ only below electron-positron annihilation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HaliaxDof;
+
+impl DegreesOfFreedom for HaliaxDof {
+    fn geff(&self, temp: f64) -> f64 {
+        self.heff(temp)
+    }
+    fn heff(&self, temp: f64) -> f64 {
+        sm_heff(temp)
+    }
+    fn sqrt_gstar(&self, temp: f64) -> f64 {
+        sm_sqrt_gstar(temp)
+    }
+    fn entropy_density(&self, temp: f64) -> f64 {
+        sm_entropy_density(temp)
+    }
+}
+
+/// Degrees of freedom interpolated linearly in `log(T)` from a table.
+/// Outside the table the end points are used.
+#[derive(Clone, Debug)]
+pub struct TabulatedDof {
+    logt: Vec<f64>,
+    geff: Vec<f64>,
+    heff: Vec<f64>,
+    dlogheff: Vec<f64>,
+}
+
+/// Temperature where neutrinos are taken to decouple instantaneously.
+const NEUTRINO_DECOUPLING_TEMP: f64 = 2e-3;
+
+/// Light hadrons `(mass, g, statistics)` making up the hadron gas below a
+/// sharp QCD transition, with antiparticles counted in `g`.
+const HADRONS: [(f64, f64, Statistics); 9] = [
+    (0.138, 3.0, Statistics::BoseEinstein),  // pi
+    (0.496, 4.0, Statistics::BoseEinstein),  // K
+    (0.548, 1.0, Statistics::BoseEinstein),  // eta
+    (0.775, 9.0, Statistics::BoseEinstein),  // rho
+    (0.783, 3.0, Statistics::BoseEinstein),  // omega
+    (0.894, 12.0, Statistics::BoseEinstein), // K*
+    (0.958, 1.0, Statistics::BoseEinstein),  // eta'
+    (1.019, 3.0, Statistics::BoseEinstein),  // phi
+    (0.939, 8.0, Statistics::FermiDirac),    // p, n
+];
+
+type WantzShellardFit = (f64, [(f64, f64, f64); 5]);
+
+/// Coefficients `(a_i, b_i, c_i)` of the fits of Wantz and Shellard,
+/// Phys. Rev. D 82 (2010) 123508, to `g_eff` and `h_eff` from lattice QCD
+/// matched to the hadron resonance gas and to perturbation theory. With
+/// `t = log(T / GeV)` the fits read `exp(a_0 + sum_i a_i (1 + tanh((t - b_i) / c_i)))`.
+const LATTICE_GEFF_FIT: WantzShellardFit = (
+    1.21,
+    [
+        (0.572, -8.77, 0.693),
+        (0.330, -2.95, 1.01),
+        (0.579, -1.80, 0.165),
+        (0.138, -0.162, 0.934),
+        (0.108, 3.76, 0.869),
+    ],
+);
+const LATTICE_HEFF_FIT: WantzShellardFit = (
+    1.36,
+    [
+        (0.498, -8.74, 0.693),
+        (0.327, -2.89, 1.01),
+        (0.579, -1.79, 0.155),
+        (0.140, -0.102, 0.963),
+        (0.109, 3.82, 0.907),
+    ],
+);
+
+lazy_static! {
+    static ref LATTICE_TABLE: TabulatedDof = compute_lattice_table();
+    static ref LATTICE_BAND: DofBand = compute_lattice_band();
+    static ref TOY_SHARP_TRANSITION_150_TABLE: TabulatedDof = compute_sharp_transition_table(0.150);
+    static ref TOY_SHARP_TRANSITION_400_TABLE: TabulatedDof = compute_sharp_transition_table(0.400);
+}
+
+/// Temperatures of the embedded tables, 50 points per decade from 10 keV to
+/// 10 TeV.
+fn table_temps() -> Vec<f64> {
+    (0..=450)
+        .map(|i| 10f64.powf(-5.0 + i as f64 / 50.0))
+        .collect()
+}
+
+/// Compute the contributions `(g_eff, h_eff)` of a species at `temp`.
+fn species_dof(temp: f64, mass: f64, g: f64, stat: Statistics) -> (f64, f64) {
+    if mass > 100.0 * temp {
+        return (0.0, 0.0);
+    }
+    let rho = equilibrium_energy_density(temp, mass, g, stat);
+    let p = equilibrium_pressure(temp, mass, g, stat);
+    (
+        30.0 * rho / (PI * PI * temp.powi(4)),
+        45.0 * (rho + p) / (2.0 * PI * PI * temp.powi(4)),
+    )
+}
+
+/// Compute `T d h_eff / dT` of a species at `temp`. With the entropy density
+/// `s = (rho + p) / T` and `ds / dT = (d rho / dT) / T` at vanishing chemical
+/// potential, this is `45 / (2 pi^2 T^4) (T d rho / dT - 3 (rho + p))`.
+fn species_dheff_dlogt(temp: f64, mass: f64, g: f64, stat: Statistics) -> f64 {
+    if mass > 100.0 * temp {
+        return 0.0;
+    }
+    let rho = equilibrium_energy_density(temp, mass, g, stat);
+    let p = equilibrium_pressure(temp, mass, g, stat);
+    let c = equilibrium_heat_capacity(temp, mass, g, stat);
+    45.0 * (temp * c - 3.0 * (rho + p)) / (2.0 * PI * PI * temp.powi(4))
+}
+
+/// Compute the total `(g_eff, h_eff)` of `species` at `temp`.
+fn sum_species_dof(temp: f64, species: &[(f64, f64, Statistics)]) -> (f64, f64) {
+    species.iter().fold((0.0, 0.0), |(g, h), &(m, dof, stat)| {
+        let (gi, hi) = species_dof(temp, m, dof, stat);
+        (g + gi, h + hi)
+    })
+}
+
+/// Compute the contributions `(g_eff, h_eff)` of the Standard Model particles
+/// without strong interactions at `temp`, treating them as an ideal gas.
+fn electroweak_dof(temp: f64) -> (f64, f64) {
+    let fermion = Statistics::FermiDirac;
+    let boson = Statistics::BoseEinstein;
+    let species = [
+        (0.0, 2.0, boson), // photon
+        (ELECTRON_MASS, 4.0, fermion),
+        (MUON_MASS, 4.0, fermion),
+        (TAU_MASS, 4.0, fermion),
+        (W_BOSON_MASS, 6.0, boson),
+        (Z_BOSON_MASS, 3.0, boson),
+        (HIGGS_MASS, 1.0, boson),
+    ];
+    let (g, h) = sum_species_dof(temp, &species);
+    // Photons and electrons share their entropy with the neutrinos until
+    // these decouple.
+    let photon_electron =
+        |temp: f64| -> f64 { 2.0 + species_dof(temp, ELECTRON_MASS, 4.0, fermion).1 };
+    let r = if temp < NEUTRINO_DECOUPLING_TEMP {
+        (photon_electron(temp) / photon_electron(NEUTRINO_DECOUPLING_TEMP)).powf(1.0 / 3.0)
+    } else {
+        1.0
+    };
+    (
+        g + 6.0 * 7.0 / 8.0 * r.powi(4),
+        h + 6.0 * 7.0 / 8.0 * r.powi(3),
+    )
+}
+
+/// Compute the contributions `(g_eff, h_eff)` of the strongly interacting
+/// particles at `temp` for an ideal gas with a sharp QCD transition at `t_c`,
+/// below which quarks and gluons are replaced by a gas of the lightest
+/// hadrons.
+fn sharp_transition_qcd_dof(temp: f64, t_c: f64) -> (f64, f64) {
+    let fermion = Statistics::FermiDirac;
+    let heavy_quarks = [
+        (CHARM_QUARK_MASS, 12.0, fermion),
+        (BOTTOM_QUARK_MASS, 12.0, fermion),
+        (TOP_QUARK_MASS, 12.0, fermion),
+    ];
+    let partons = [
+        (0.0, 16.0, Statistics::BoseEinstein), // gluons
+        (UP_QUARK_MASS, 12.0, fermion),
+        (DOWN_QUARK_MASS, 12.0, fermion),
+        (STRANGE_QUARK_MASS, 12.0, fermion),
+    ];
+    let (g, h) = sum_species_dof(temp, &heavy_quarks);
+    let (gl, hl) = if temp > t_c {
+        sum_species_dof(temp, &partons)
+    } else {
+        sum_species_dof(temp, &HADRONS)
+    };
+    (g + gl, h + hl)
+}
+
+/// Evaluate a fit of Wantz and Shellard at `temp`.
+fn wantz_shellard_fit(fit: &WantzShellardFit, temp: f64) -> f64 {
+    let t = temp.ln();
+    let (a0, terms) = fit;
+    let sum: f64 = terms
+        .iter()
+        .map(|(a, b, c)| a * (1.0 + ((t - b) / c).tanh()))
+        .sum();
+    (a0 + sum).exp()
+}
+
+/// Tabulate the fits of Wantz and Shellard.
+fn compute_lattice_table() -> TabulatedDof {
+    let temps = table_temps();
+    let geff: Vec<f64> = temps
+        .iter()
+        .map(|&temp| wantz_shellard_fit(&LATTICE_GEFF_FIT, temp))
+        .collect();
+    let heff: Vec<f64> = temps
+        .iter()
+        .map(|&temp| wantz_shellard_fit(&LATTICE_HEFF_FIT, temp))
+        .collect();
+    TabulatedDof::new(&temps, &geff, &heff).expect("lattice table is valid")
+}
+
+/// Band around the fits of Wantz and Shellard given by their relative
+/// difference in `h_eff` from the tables of `haliax-thermal-functions`,
+/// applied to both `g_eff` and `h_eff`.
+fn compute_lattice_band() -> DofBand {
+    let temps = table_temps();
+    let lattice = &*LATTICE_TABLE;
+    let (mut geff, mut geff_err, mut heff, mut heff_err) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for &temp in temps.iter() {
+        let (g, h) = (lattice.geff(temp), lattice.heff(temp));
+        let rel = (HaliaxDof.heff(temp) / h - 1.0).abs().min(0.5);
+        geff.push(g);
+        geff_err.push(rel * g);
+        heff.push(h);
+        heff_err.push(rel * h);
+    }
+    DofBand::from_errors(&temps, &geff, &geff_err, &heff, &heff_err).expect("lattice band is valid")
+}
+
+/// Tabulate the ideal gas with a sharp QCD transition at `t_c`.
+fn compute_sharp_transition_table(t_c: f64) -> TabulatedDof {
+    let temps = table_temps();
+    let (geff, heff): (Vec<f64>, Vec<f64>) = temps
+        .iter()
+        .map(|&temp| {
+            let (g_ew, h_ew) = electroweak_dof(temp);
+            let (g_qcd, h_qcd) = sharp_transition_qcd_dof(temp, t_c);
+            (g_ew + g_qcd, h_ew + h_qcd)
+        })
+        .unzip();
+    TabulatedDof::new(&temps, &geff, &heff).expect("sharp-transition table is valid")
+}
+
+/// Embedded tables of the Standard Model degrees of freedom.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DofTable {
+    /// Fits of Wantz and Shellard (2010) to lattice QCD, matched to the
+    /// hadron resonance gas below and to perturbation theory above the
+    /// crossover. No uncertainty is quoted for the fits, so their band is
+    /// the systematic one of `DofTable::band`.
+    Lattice,
+    /// Toy model, not a literature table: an ideal gas with a sharp QCD
+    /// transition at 150 MeV, below which quarks and gluons are replaced by
+    /// the lightest hadrons. This mimics the treatment of Srednicki, Watkins
+    /// and Olive (1988) and of Gondolo and Gelmini (1991) but is computed
+    /// here rather than taken from their tables.
+    ToySharpTransition150MeV,
+    /// Toy model as `ToySharpTransition150MeV` with the transition at 400 MeV.
+    ToySharpTransition400MeV,
+}
+
+impl DofTable {
+    /// Embedded tables taken from the literature, which
+    /// `KineticMixing::relic_density_qcd_range` sweeps.
+    pub const LITERATURE: [DofTable; 1] = [DofTable::Lattice];
+    /// All embedded tables, including the toy models.
+    pub const ALL: [DofTable; 3] = [
+        DofTable::Lattice,
+        DofTable::ToySharpTransition150MeV,
+        DofTable::ToySharpTransition400MeV,
+    ];
+    /// Get the table. The tables are computed once and shared.
+    pub fn table(self) -> TabulatedDof {
+        match self {
+            DofTable::Lattice => LATTICE_TABLE.clone(),
+            DofTable::ToySharpTransition150MeV => TOY_SHARP_TRANSITION_150_TABLE.clone(),
+            DofTable::ToySharpTransition400MeV => TOY_SHARP_TRANSITION_400_TABLE.clone(),
+        }
+    }
+    /// Get the table with its uncertainty band. For `Lattice` this is the
+    /// relative difference in `h_eff` from the independent tables of
+    /// `haliax-thermal-functions`, a systematic estimate rather than a
+    /// published error, which is largest across the QCD crossover. The toy
+    /// models have no band.
+    pub fn band(self) -> DofBand {
+        match self {
+            DofTable::Lattice => LATTICE_BAND.clone(),
+            _ => DofBand::exact(self.table()),
+        }
+    }
+}
+
+/// Degrees of freedom together with the lower and upper edges of their
+/// uncertainty band, e.g. a lattice QCD table with its quoted errors.
+#[derive(Clone, Debug)]
+pub struct DofBand {
+    /// Central values.
+    pub central: TabulatedDof,
+    /// Lower edge of the band for both `g_eff` and `h_eff`.
+    pub lower: TabulatedDof,
+    /// Upper edge of the band for both `g_eff` and `h_eff`.
+    pub upper: TabulatedDof,
+}
+
+impl DofBand {
+    /// Create a band without uncertainty.
+    pub fn exact(table: TabulatedDof) -> DofBand {
+        DofBand {
+            central: table.clone(),
+            lower: table.clone(),
+            upper: table,
+        }
+    }
+    /// Create a band from the central values of `g_eff` and `h_eff` at
+    /// `temps` and their errors, in the form published with lattice tables.
+    /// The edges are the central values minus and plus the errors. Returns an
+    /// error if a table is invalid, see `TabulatedDof::new`, or an error is
+    /// negative.
+    pub fn from_errors(
+        temps: &[f64],
+        geff: &[f64],
+        geff_err: &[f64],
+        heff: &[f64],
+        heff_err: &[f64],
+    ) -> Result<DofBand, ModelError> {
+        for &err in geff_err.iter().chain(heff_err.iter()) {
+            check_parameter("error of the degrees of freedom", err, ">= 0", |e| e >= 0.0)?;
+        }
+        let shift = |ys: &[f64], errs: &[f64], sign: f64| -> Vec<f64> {
+            ys.iter()
+                .zip(errs.iter())
+                .map(|(y, e)| y + sign * e)
+                .collect()
+        };
+        Ok(DofBand {
+            central: TabulatedDof::new(temps, geff, heff)?,
+            lower: TabulatedDof::new(
+                temps,
+                &shift(geff, geff_err, -1.0),
+                &shift(heff, heff_err, -1.0),
+            )?,
+            upper: TabulatedDof::new(
+                temps,
+                &shift(geff, geff_err, 1.0),
+                &shift(heff, heff_err, 1.0),
+            )?,
+        })
+    }
+    /// Get the central table and the two edges of the band.
+    pub fn tables(&self) -> [&TabulatedDof; 3] {
+        [&self.central, &self.lower, &self.upper]
+    }
+}
+
+impl TabulatedDof {
+    /// Create a table from the temperatures `temps`, in increasing order, and
+    /// the corresponding `g_eff` and `h_eff`. Returns an error unless the
+    /// table has at least two rows of equal length, the temperatures are
+    /// positive and strictly increasing and all entries are finite and
+    /// positive.
+    pub fn new(temps: &[f64], geff: &[f64], heff: &[f64]) -> Result<TabulatedDof, ModelError> {
+        let n = temps.len();
+        check_parameter("number of temperatures", n as f64, ">= 2", |n| n >= 2.0)?;
+        check_parameter(
+            "number of g_eff",
+            geff.len() as f64,
+            "one per temperature",
+            |m| m == n as f64,
+        )?;
+        check_parameter(
+            "number of h_eff",
+            heff.len() as f64,
+            "one per temperature",
+            |m| m == n as f64,
+        )?;
+        for (i, &temp) in temps.iter().enumerate() {
+            let previous = if i > 0 { temps[i - 1] } else { 0.0 };
+            check_parameter("temperature", temp, "> 0 and increasing", |t| t > previous)?;
+        }
+        for &g in geff.iter() {
+            check_parameter("g_eff", g, "> 0", |g| g > 0.0)?;
+        }
+        for &h in heff.iter() {
+            check_parameter("h_eff", h, "> 0", |h| h > 0.0)?;
+        }
+        let logt: Vec<f64> = temps.iter().map(|t| t.ln()).collect();
+        let dlogheff = (0..n)
+            .map(|i| {
+                let (lo, hi) = (i.saturating_sub(1), (i + 1).min(n - 1));
+                (heff[hi].ln() - heff[lo].ln()) / (logt[hi] - logt[lo])
+            })
+            .collect();
+        Ok(TabulatedDof {
+            logt,
+            geff: geff.to_vec(),
+            heff: heff.to_vec(),
+            dlogheff,
+        })
+    }
+    /// Interpolate `ys` linearly in `log(T)`. Returns NaN if `temp` is NaN or
+    /// negative.
+    fn interpolate(&self, ys: &[f64], temp: f64) -> f64 {
+        let logt = temp.ln();
+        if logt.is_nan() {
+            return f64::NAN;
+        }
+        let n = self.logt.len();
+        if logt <= self.logt[0] {
+            return ys[0];
+        }
+        if logt >= self.logt[n - 1] {
+            return ys[n - 1];
+        }
+        let i = self.logt.partition_point(|&t| t <= logt) - 1;
+        let f = (logt - self.logt[i]) / (self.logt[i + 1] - self.logt[i]);
+        ys[i] + f * (ys[i + 1] - ys[i])
+    }
+}
+
+impl DegreesOfFreedom for TabulatedDof {
+    fn geff(&self, temp: f64) -> f64 {
+        self.interpolate(&self.geff, temp)
+    }
+    fn heff(&self, temp: f64) -> f64 {
+        self.interpolate(&self.heff, temp)
+    }
+    fn dlogheff_dlogt(&self, temp: f64) -> f64 {
+        self.interpolate(&self.dlogheff, temp)
+    }
+}
+
+/// Degrees of freedom of a bath to which additional species in kinetic and
+/// chemical equilibrium with it contribute, such as a light dark sector.
+#[derive(Clone)]
+pub struct ExtendedDof {
+    /// Degrees of freedom of the bath without the additional species.
+    pub base: Arc<dyn DegreesOfFreedom + Send + Sync>,
+    /// Additional species, sharing the temperature of the bath.
+    pub species: Vec<Species>,
+}
+
+impl ExtendedDof {
+    pub fn new(
+        base: Arc<dyn DegreesOfFreedom + Send + Sync>,
+        species: Vec<Species>,
+    ) -> ExtendedDof {
+        ExtendedDof { base, species }
+    }
+    /// Compute the contributions `(g_eff, h_eff)` of the additional species.
+    fn extra(&self, temp: f64) -> (f64, f64) {
+        self.species.iter().fold((0.0, 0.0), |(g, h), sp| {
+            let (gi, hi) = species_dof(temp, sp.mass, sp.g, sp.statistics);
+            (g + gi, h + hi)
+        })
+    }
+}
+
+impl DegreesOfFreedom for ExtendedDof {
+    fn geff(&self, temp: f64) -> f64 {
+        self.base.geff(temp) + self.extra(temp).0
+    }
+    fn heff(&self, temp: f64) -> f64 {
+        self.base.heff(temp) + self.extra(temp).1
+    }
+    /// Combine the derivative of the base bath with the analytic derivative
+    /// of each species, weighted by their `h_eff`.
+    fn dlogheff_dlogt(&self, temp: f64) -> f64 {
+        let hbase = self.base.heff(temp);
+        let dh: f64 = self
+            .species
+            .iter()
+            .map(|sp| species_dheff_dlogt(temp, sp.mass, sp.g, sp.statistics))
+            .sum();
+        (hbase * self.base.dlogheff_dlogt(temp) + dh) / (hbase + self.extra(temp).1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dof_table_limits() {
+        for table in DofTable::ALL.iter() {
+            let dof = table.table();
+            // Photons and decoupled neutrinos today, h = 2 + 7/8 * 6 * 4/11.
+            assert!((dof.heff(1e-5) - 3.909).abs() < 2e-2);
+            // Full Standard Model.
+            assert!((dof.geff(1e4) - 106.75).abs() < 1.0);
+        }
+        // The treatments of the QCD transition differ across it.
+        let heff: Vec<f64> = DofTable::ALL.iter().map(|t| t.table().heff(0.2)).collect();
+        let max = heff.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let min = heff.iter().cloned().fold(f64::INFINITY, f64::min);
+        assert!(max - min > 1.0);
+    }
+
+    #[test]
+    fn test_tabulated_dof() {
+        let dof = TabulatedDof::new(&[1.0, 10.0], &[10.0, 100.0], &[10.0, 100.0]).unwrap();
+        assert!((dof.heff(10f64.sqrt()) - 55.0).abs() < 1e-12);
+        assert_eq!(dof.heff(1e-3), 10.0);
+        assert_eq!(dof.heff(10.0), 100.0);
+        assert!(dof.heff(f64::NAN).is_nan());
+        assert!(dof.geff(-1.0).is_nan());
+        // Invalid tables are rejected.
+        assert!(TabulatedDof::new(&[1.0], &[10.0], &[10.0]).is_err());
+        assert!(TabulatedDof::new(&[1.0, 10.0], &[10.0], &[10.0, 100.0]).is_err());
+        assert!(TabulatedDof::new(&[10.0, 1.0], &[10.0, 100.0], &[10.0, 100.0]).is_err());
+        assert!(TabulatedDof::new(&[1.0, f64::NAN], &[10.0, 100.0], &[10.0, 100.0]).is_err());
+        assert!(TabulatedDof::new(&[1.0, 10.0], &[10.0, 100.0], &[0.0, 100.0]).is_err());
+    }
+
+    #[test]
+    fn test_dof_band() {
+        let band = DofBand::from_errors(
+            &[1.0, 10.0],
+            &[10.0, 100.0],
+            &[1.0, 5.0],
+            &[10.0, 100.0],
+            &[2.0, 5.0],
+        )
+        .unwrap();
+        assert_eq!(band.lower.geff(1.0), 9.0);
+        assert_eq!(band.upper.heff(10.0), 105.0);
+        // Errors must be non-negative and the lower edge must stay positive.
+        assert!(DofBand::from_errors(
+            &[1.0, 10.0],
+            &[10.0, 100.0],
+            &[-1.0, 5.0],
+            &[10.0, 100.0],
+            &[2.0, 5.0]
+        )
+        .is_err());
+        assert!(DofBand::from_errors(
+            &[1.0, 10.0],
+            &[10.0, 100.0],
+            &[1.0, 5.0],
+            &[10.0, 100.0],
+            &[10.0, 5.0]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_lattice_band() {
+        let band = DofTable::Lattice.band();
+        // The band is open across the QCD crossover and closes where all
+        // tables agree on the free Standard Model.
+        assert!(band.upper.heff(0.3) - band.lower.heff(0.3) > 0.1);
+        assert!(band.lower.heff(0.3) < band.central.heff(0.3));
+        assert!(band.upper.heff(1e4) / band.lower.heff(1e4) - 1.0 < 0.05);
+    }
+
+    #[test]
+    fn test_extended_dof() {
+        let species = vec![Species::new("v", 1e-3, 3.0, Statistics::BoseEinstein)];
+        let dof = ExtendedDof::new(Arc::new(HaliaxDof), species);
+        // A relativistic boson adds its internal degrees of freedom...
+        assert!((dof.heff(10.0) - HaliaxDof.heff(10.0) - 3.0).abs() < 1e-3);
+        // ...and nothing once it is non-relativistic.
+        assert!((dof.heff(1e-5) - HaliaxDof.heff(1e-5)).abs() < 1e-10);
+
+        // The analytic derivative agrees with a central difference while the
+        // species becomes non-relativistic on top of a constant bath.
+        let flat = TabulatedDof::new(&[1e-6, 1e6], &[10.0, 10.0], &[10.0, 10.0]).unwrap();
+        let species = vec![
+            Species::new("v", 1.0, 3.0, Statistics::BoseEinstein),
+            Species::new("chi", 3.0, 4.0, Statistics::FermiDirac),
+        ];
+        let dof = ExtendedDof::new(Arc::new(flat), species);
+        for &temp in [0.1, 0.3, 1.0, 3.0, 10.0].iter() {
+            // The step keeps the 1e-8 error of the momentum integrals below
+            // 1e-5 in the difference.
+            let h = 1e-3;
+            let numerical = ((dof.heff(temp * (1.0 + h))).ln() - (dof.heff(temp * (1.0 - h))).ln())
+                / ((1.0 + h) / (1.0 - h)).ln();
+            let analytic = dof.dlogheff_dlogt(temp);
+            assert!(analytic > 0.0);
+            assert!((analytic - numerical).abs() < 2e-5);
+        }
+    }
+}
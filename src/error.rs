@@ -0,0 +1,110 @@
+//! Errors reported by the dark matter models.
+
+use cyphus_diffeq::prelude::*;
+use ndarray::prelude::*;
+use std::fmt;
+
+/// Reasons a model cannot be constructed or a quantity cannot be computed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModelError {
+    /// A model parameter is outside of its allowed range.
+    InvalidParameter {
+        name: &'static str,
+        value: f64,
+        expected: &'static str,
+    },
+    /// A thermal average returned a non-finite value or error estimate at
+    /// `x = mass / temperature`.
+    ThermalAverageFailed { x: f64, val: f64, err: f64 },
+    /// The ODE integrator stopped at `reached` before reaching `target`,
+    /// e.g. because the step size collapsed.
+    IntegrationIncomplete { reached: f64, target: f64 },
+    /// The solution of the Boltzmann equation became non-finite at `x`.
+    NonFiniteAbundance { x: f64 },
+    /// No temperature was found at which the bath has the given value of
+    /// `quantity`, e.g. its entropy density.
+    TemperatureNotFound { quantity: &'static str, value: f64 },
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::InvalidParameter {
+                name,
+                value,
+                expected,
+            } => write!(f, "invalid {} = {}: expected {}", name, value, expected),
+            ModelError::ThermalAverageFailed { x, val, err } => write!(
+                f,
+                "thermal average failed at x = {}: value {} with error {}",
+                x, val, err
+            ),
+            ModelError::IntegrationIncomplete { reached, target } => write!(
+                f,
+                "ODE integration stopped at x = {} before reaching x = {}",
+                reached, target
+            ),
+            ModelError::NonFiniteAbundance { x } => {
+                write!(f, "abundance became non-finite at x = {}", x)
+            }
+            ModelError::TemperatureNotFound { quantity, value } => write!(
+                f,
+                "no temperature found at which the {} of the bath is {}",
+                quantity, value
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+/// Check that `value` is finite and satisfies `ok`, reporting it as the
+/// parameter `name` otherwise.
+pub(crate) fn check_parameter<F>(
+    name: &'static str,
+    value: f64,
+    expected: &'static str,
+    ok: F,
+) -> Result<(), ModelError>
+where
+    F: Fn(f64) -> bool,
+{
+    if value.is_finite() && ok(value) {
+        Ok(())
+    } else {
+        Err(ModelError::InvalidParameter {
+            name,
+            value,
+            expected,
+        })
+    }
+}
+
+/// Check that every component of the ODE solution `sol` is finite and that
+/// the integration reached `t_end`, which corresponds to `x = target`. The
+/// point where the check fails is reported through `x_of`, which gives
+/// `x = mass / temperature` at a point `(t, u)` of the solution.
+pub(crate) fn check_solution<F>(
+    sol: &OdeSolution,
+    t_end: f64,
+    target: f64,
+    x_of: F,
+) -> Result<(), ModelError>
+where
+    F: Fn(f64, ArrayView1<f64>) -> f64,
+{
+    for (&t, u) in sol.ts.iter().zip(sol.us.iter()) {
+        if u.iter().any(|v| !v.is_finite()) {
+            return Err(ModelError::NonFiniteAbundance {
+                x: x_of(t, u.view()),
+            });
+        }
+    }
+    match (sol.ts.last(), sol.us.last()) {
+        (Some(&t), Some(_)) if t >= t_end - 1e-8 * t_end.abs().max(1.0) => Ok(()),
+        (t, u) => Err(ModelError::IntegrationIncomplete {
+            reached: t.zip(u).map_or(f64::NAN, |(&t, u)| x_of(t, u.view())),
+            target,
+        }),
+    }
+}
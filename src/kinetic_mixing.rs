@@ -1,11 +1,30 @@
+pub mod asymmetric;
 pub mod boltzmann;
+pub mod bound_states;
+pub mod builder;
 pub mod cross_sections;
+pub mod dense_output;
+pub mod dilution;
+pub mod instrumentation;
+pub mod propagator;
+pub mod reheating;
+pub mod relic_result;
+pub mod sommerfeld;
 pub mod thermal_cross_section;
+pub mod velocity_expansion;
 pub mod widths;
 
-use cyphus_integration::prelude::*;
+use crate::cosmology::Cosmology;
+use crate::error::ModelError;
+use crate::sm_parameters::SmParameters;
+use crate::statistics::Statistics;
+pub use builder::KineticMixingBuilder;
+use instrumentation::CounterHandle;
+use propagator::WidthScheme;
+use sommerfeld::{Sommerfeld, SommerfeldCache};
+use std::sync::Arc;
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum KineticMixingFinalStates {
     All,
     XX,
@@ -22,103 +41,923 @@ pub enum KineticMixingFinalStates {
     NumuNumu,
     NutauNutau,
     HiggsZ,
+    VV,
+    VFF,
+    WW,
+    VZ,
 }
 
+/// Annihilation channels making up `KineticMixingFinalStates::All`.
+const ANNIHILATION_CHANNELS: [KineticMixingFinalStates; 17] = [
+    KineticMixingFinalStates::UU,
+    KineticMixingFinalStates::CC,
+    KineticMixingFinalStates::TT,
+    KineticMixingFinalStates::DD,
+    KineticMixingFinalStates::SS,
+    KineticMixingFinalStates::BB,
+    KineticMixingFinalStates::EE,
+    KineticMixingFinalStates::MuMu,
+    KineticMixingFinalStates::TauTau,
+    KineticMixingFinalStates::NueNue,
+    KineticMixingFinalStates::NumuNumu,
+    KineticMixingFinalStates::NutauNutau,
+    KineticMixingFinalStates::HiggsZ,
+    KineticMixingFinalStates::VV,
+    KineticMixingFinalStates::VFF,
+    KineticMixingFinalStates::WW,
+    KineticMixingFinalStates::VZ,
+];
+
+/// Distances from the `V` resonance, in units of its width, at which
+/// breakpoints are placed for the thermal average.
+const RESONANCE_BREAKPOINTS: [f64; 3] = [1.0, 10.0, thermal_cross_section::NARROW_WIDTH_WINDOW];
+
+/// Dark matter chi coupled to a dark photon `V` which kinetically mixes with
+/// the hypercharge gauge boson.
+///
+/// The masses and couplings are private so that the width of `V` and the
+/// breakpoints of the thermal-average integrator are recomputed whenever they
+/// change; use the setters or `KineticMixingBuilder`.
 #[derive(Clone)]
 pub struct KineticMixing {
-    pub mx: f64,
-    pub mv: f64,
-    pub gvxx: f64,
-    pub eps: f64,
-    pub widthv: f64,
-    gk: GaussKronrodIntegrator,
+    mx: f64,
+    mv: f64,
+    gvxx: f64,
+    eps: f64,
+    widthv: f64,
+    width_override: Option<f64>,
+    /// Ratio of `widthv` to the width computed from the couplings, which
+    /// normalizes the energy-dependent width of `WidthScheme::Running`. It is
+    /// `None` if the width is fixed but `V` cannot decay at `mv`, in which
+    /// case the fixed width is used at all energies.
+    running_width_scale: Option<f64>,
+    sm: SmParameters,
+    /// Expansion history used when solving the Boltzmann equation. It also
+    /// carries the degrees of freedom of the bath, e.g.
+    /// `RadiationDomination::new(Arc::new(DofTable::Lattice.table()))`.
+    pub cosmology: Arc<dyn Cosmology + Send + Sync>,
+    /// Include `V <-> chi + chibar` decays and inverse decays, with `V` in
+    /// equilibrium, in the Boltzmann equation. The on-shell part of the
+    /// s-channel annihilations is then counted twice near the resonance, so
+    /// this is intended for `mv > 2 mx` where inverse decays dominate.
+    pub inverse_decays: bool,
+    /// Statistics of chi used for its equilibrium density and the thermal
+    /// average of the annihilation cross section.
+    pub statistics: Statistics,
+    /// Treatment of the width in the s-channel `V` propagator.
+    pub width_scheme: WidthScheme,
+    /// Sommerfeld enhancement applied to the annihilation cross section.
+    pub sommerfeld: Sommerfeld,
+    /// Include the formation and decay of chi-chibar bound states as an
+    /// effective annihilation channel in the thermal cross section.
+    pub bound_states: bool,
+    epsrel: f64,
+    ode_reltol: f64,
+    ode_abstol: f64,
+    counters: CounterHandle,
+    singular_points: Vec<f64>,
+    sommerfeld_table: SommerfeldCache,
 }
 
 impl KineticMixing {
-    #[allow(dead_code)]
-    pub fn new(mx: f64, mv: f64, gvxx: f64, eps: f64) -> KineticMixing {
-        let resonance = mv / mx;
-        let threshold = 2.0 * mv / mx;
-
-        let singular_points = if threshold > 2.0 {
-            if resonance > 2.0 {
-                vec![resonance, threshold]
-            } else {
-                vec![threshold]
-            }
-        } else {
-            vec![]
-        };
+    /// Create the model with dark matter mass `mx`, mediator mass `mv`, dark
+    /// coupling `gvxx` and kinetic-mixing parameter `eps`, checking that the
+    /// masses are positive, the coupling is non-negative and `0 <= eps < 1`.
+    /// Use `KineticMixingBuilder` to set the other options.
+    pub fn new(mx: f64, mv: f64, gvxx: f64, eps: f64) -> Result<KineticMixing, ModelError> {
+        KineticMixingBuilder::new(mx, mv, gvxx, eps).build()
+    }
+    /// Compute the breakpoints in `z = sqrt(s) / mx` for thermal averages.
+    /// Breakpoints are placed at the `V V` threshold, where `V f fbar` closes,
+    /// and at the `V` resonance, where `V f fbar` opens, together with points
+    /// displaced from the resonance by multiples of the width so that narrow
+    /// resonances are resolved.
+    fn compute_singular_points(&self) -> Vec<f64> {
+        let resonance = self.mv / self.mx;
+        let threshold = 2.0 * self.mv / self.mx;
 
-        let gk = GaussKronrodIntegratorBuilder::default()
-            .epsabs(0.0)
-            .epsrel(1e-8)
-            .singular_points(singular_points)
-            .limit(1000)
-            .build();
-
-        let mut km = KineticMixing {
-            mx,
-            mv,
-            gvxx,
-            eps,
-            widthv: 0.0,
-            gk,
-        };
-        km.widthv = km.vm_decay_width();
-        km
+        let mut singular_points = vec![];
+        if resonance > 2.0 {
+            singular_points.push(resonance);
+            for k in RESONANCE_BREAKPOINTS.iter() {
+                let dz = k * self.widthv / self.mx;
+                singular_points.push(resonance - dz);
+                singular_points.push(resonance + dz);
+            }
+        }
+        if threshold > 2.0 {
+            singular_points.push(threshold);
+        }
+        singular_points.retain(|&z| z > 2.0);
+        singular_points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        singular_points.dedup();
+        singular_points
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::time::{Duration, Instant};
+    use crate::cosmology::RadiationDomination;
 
     #[test]
     fn test_cs() {
-        let km = KineticMixing::new(1e3, 1e4, 1.0, 1e-3);
-        println!("{}", km.annihilation_cross_section(3e3));
+        let km = KineticMixing::new(1e3, 1e4, 1.0, 1e-3).unwrap();
+        let sigma = km.annihilation_cross_section(3e3);
+        assert!(sigma.is_finite() && sigma > 0.0);
+    }
+
+    #[test]
+    fn test_cs_channels_sum_to_all() {
+        for &(mx, mv) in [(1e3, 1e4), (1e3, 1e2), (50.0, 120.0)].iter() {
+            let km = KineticMixing::new(mx, mv, 1.0, 1e-3).unwrap();
+            for &cme in [2.1 * mx, 3.0 * mx, 10.0 * mx].iter() {
+                let all = km.partial_annihilation_cross_section(cme, KineticMixingFinalStates::All);
+                let sum: f64 = ANNIHILATION_CHANNELS
+                    .iter()
+                    .map(|fs| km.partial_annihilation_cross_section(cme, *fs))
+                    .sum();
+                assert!((sum - all).abs() <= 1e-12 * all.abs());
+            }
+        }
     }
 
     #[test]
     fn test_tcs() {
-        let km = KineticMixing::new(1e3, 1e4, 1.0, 1e-3);
-        println!("{}", km.thermal_cross_section(1.0));
+        let km = KineticMixing::new(1e3, 1e4, 1.0, 1e-3).unwrap();
+        let tcs = km.thermal_cross_section_with_error(1.0);
+        assert!(tcs.converged);
+        assert!(tcs.val.is_finite() && tcs.val > 0.0);
     }
 
     #[test]
-    fn test_boltz() {
-        let km = KineticMixing::new(1e3, 1e4, 1.0, 1e-1);
-        let sol = km.solve_boltzmann();
+    fn test_tcs_statistics() {
+        let mut km = KineticMixing::new(10.0, 25.0, 1.0, 1e-3).unwrap();
+        for x in [1.0, 3.0, 10.0, 20.0, 30.0].iter() {
+            let mut tcs = Vec::new();
+            for stat in [
+                Statistics::MaxwellBoltzmann,
+                Statistics::FermiDirac,
+                Statistics::BoseEinstein,
+            ]
+            .iter()
+            {
+                km.statistics = *stat;
+                tcs.push(km.thermal_cross_section(*x));
+            }
+            let (fd, be) = (tcs[1] / tcs[0] - 1.0, tcs[2] / tcs[0] - 1.0);
+            if *x == 1.0 {
+                // Quantum statistics matter for a relativistic chi, with FD
+                // and BE on opposite sides of MB.
+                assert!(fd.abs() > 0.01 && be.abs() > 0.01);
+                assert!(fd * be < 0.0);
+            } else if *x >= 20.0 {
+                // By freeze-out chi is non-relativistic and the three agree.
+                assert!(fd.abs() < 1e-6 && be.abs() < 1e-6);
+            }
+        }
+    }
 
-        for t in sol.ts.iter() {
-            println!("{},", t);
+    #[test]
+    fn test_tcs_narrow_resonance() {
+        let mut km = KineticMixing::new(1e3, 3e3, 1e-2, 1e-6).unwrap();
+        for x in [1.0, 10.0, 100.0].iter() {
+            let tcs = km.thermal_cross_section_with_error(*x);
+            assert!(tcs.converged);
         }
-        for u in sol.us.iter() {
-            println!("{},", u[0]);
+        // Quantum statistics resolve the resonance in the same way and agree
+        // with Maxwell-Boltzmann statistics once chi is non-relativistic.
+        let mb = km.thermal_cross_section(20.0);
+        km.statistics = Statistics::FermiDirac;
+        for x in [1.0, 10.0].iter() {
+            assert!(km.thermal_cross_section_with_error(*x).converged);
         }
+        assert!((km.thermal_cross_section(20.0) / mb - 1.0).abs() < 1e-6);
+
+        // The pole mass is shifted by a relative widthv^2 / mv^2 ~ 1e-11, so
+        // the window gives the same result in all schemes.
+        km.statistics = Statistics::MaxwellBoltzmann;
+        km.width_scheme = WidthScheme::PoleMass;
+        assert!((km.thermal_cross_section(20.0) / mb - 1.0).abs() < 1e-6);
+
+        // A stable V has no window to integrate analytically.
+        let km = KineticMixingBuilder::new(1e3, 3e3, 1e-2, 0.0)
+            .width(0.0)
+            .build()
+            .unwrap();
+        let sigmav = km.thermal_cross_section(10.0);
+        assert!(sigmav.is_finite() && sigmav >= 0.0);
     }
+
     #[test]
-    fn test_rd() {
-        let now = Instant::now();
-        let km = KineticMixing::new(1e3, 1e2, 1.0, 1e-3);
-        println!("{}, {}", km.relic_density(), now.elapsed().as_millis());
+    fn test_rd_width_scheme() {
+        let mut km = KineticMixing::new(1e3, 2.05e3, 1.0, 1e-3).unwrap();
+        for scheme in [
+            WidthScheme::Fixed,
+            WidthScheme::Running,
+            WidthScheme::PoleMass,
+        ]
+        .iter()
+        {
+            km.width_scheme = *scheme;
+            let omega = km.relic_density().unwrap().omega;
+            assert!(omega.is_finite() && omega > 0.0);
+        }
+        // A fixed width sets the running width on the resonance.
+        let mv2 = km.mv * km.mv;
+        for &eps in [1e-3, 0.0].iter() {
+            km.set_eps(eps).unwrap();
+            km.set_width(Some(10.0)).unwrap();
+            km.width_scheme = WidthScheme::Running;
+            let running = km.propagator_denominator(mv2);
+            km.width_scheme = WidthScheme::Fixed;
+            assert!((running / km.propagator_denominator(mv2) - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_velocity_expansion() {
+        // The reference is the exact cross section of the same channel at
+        // v = 1e-2, where the neglected O(v^4) terms are of relative size
+        // 1e-8.
+        let km = KineticMixing::new(1e3, 5e2, 1.0, 1e-3).unwrap();
+        let v: f64 = 1e-2;
+        let cme = 2.0 * km.mx / (1.0 - 0.25 * v * v).sqrt();
+        for fs in [
+            KineticMixingFinalStates::MuMu,
+            KineticMixingFinalStates::BB,
+            KineticMixingFinalStates::VV,
+        ]
+        .iter()
+        {
+            let exp = km.velocity_expansion(*fs);
+            let sigmav = km.partial_annihilation_cross_section(cme, *fs) * v;
+            assert!(((exp.a + exp.b * v * v) / sigmav - 1.0).abs() < 1e-6);
+        }
+        // With running, the couplings and masses of the expansion are those at
+        // 2 mx, which the cross section approaches at small velocities.
+        let mut km = km;
+        km.set_sm_parameters(SmParameters::default().with_running(true))
+            .unwrap();
+        for fs in [KineticMixingFinalStates::MuMu, KineticMixingFinalStates::BB].iter() {
+            let exp = km.velocity_expansion(*fs);
+            let sigmav = km.partial_annihilation_cross_section(cme, *fs) * v;
+            assert!(exp
+                .warnings
+                .contains(&velocity_expansion::ExpansionWarning::RunningCouplings));
+            assert!(((exp.a + exp.b * v * v) / sigmav - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_tcs_large_x() {
+        let km = KineticMixing::new(1e3, 1e2, 1.0, 1e-3).unwrap();
+        let fs = KineticMixingFinalStates::VV;
+        let exp = km.velocity_expansion(fs);
+        // <sigma v> = a (1 - 3 / (2 x)) + 6 b / x + O(1 / x^2) for the
+        // relative velocity defined through s = 4 mx^2 / (1 - v^2 / 4),
+        // from expanding the single-integral average of Gondolo and Gelmini,
+        // Nucl. Phys. B 360 (1991) 145, to first order in 1 / x. The
+        // reference is analytic, so the tolerance only has to cover the
+        // O(1 / x^2) terms, below 1e-7 for x >= 1e4.
+        for x in [1e4, 1e5, 1e6].iter() {
+            let tcs = km.thermal_partial_cross_section(*x, fs);
+            let approx = exp.a * (1.0 - 1.5 / x) + 6.0 * exp.b / x;
+            assert!((tcs / approx - 1.0).abs() < 1e-6);
+        }
+
+        // The switch to the non-relativistic form is continuous.
+        let below = km.thermal_cross_section(100.0 * (1.0 - 1e-12));
+        let above = km.thermal_cross_section(100.0);
+        assert!((below / above - 1.0).abs() < 1e-7);
+    }
+
+    #[test]
+    fn test_rd_three_body() {
+        // The relic density should vary smoothly as mv crosses mx. Near mx
+        // it changes by about 30% per percent in mv as chi chibar -> V V
+        // closes, so across 2e-6 it changes by about 6e-5. A jump in the
+        // cross section at the threshold would show up well above that and
+        // the 1e-7 tolerance of the ODE solver.
+        let omega: Vec<f64> = [1e3 * (1.0 - 1e-6), 1e3 * (1.0 + 1e-6)]
+            .iter()
+            .map(|mv| {
+                let km = KineticMixing::new(1e3, *mv, 1.0, 1e-3).unwrap();
+                km.relic_density().unwrap().omega
+            })
+            .collect();
+        assert!((omega[1] / omega[0] - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_cs_vv_threshold() {
+        // The total cross section is continuous where V V opens, with the
+        // on-shell part of V f fbar moving into V V. sigma_xx_to_vv does not
+        // depend on eps, so it dominates the total right above the
+        // threshold, and grows as the square root of the distance to it.
+        // The off-shell integrals on both sides agree to the integration
+        // tolerance, and the subtracted on-shell part is suppressed by
+        // BR(V -> SM) ~ 1e-6, so the total changes by sigma_xx_to_vv alone.
+        let km = KineticMixing::new(1e3, 1.5e3, 1.0, 1e-3).unwrap();
+        let threshold = 2.0 * km.mv;
+        let jumps: Vec<f64> = [1e-8, 1e-11]
+            .iter()
+            .map(|d| {
+                let below = km.annihilation_cross_section(threshold * (1.0 - d));
+                let above = km.annihilation_cross_section(threshold * (1.0 + d));
+                let vv = km.sigma_xx_to_vv(threshold * (1.0 + d));
+                assert!(((above - below) / vv - 1.0).abs() < 1e-4);
+                above - below
+            })
+            .collect();
+        assert!(jumps[1] < jumps[0]);
+        // Far above the threshold the off-shell remainder is small.
+        let cme = 2.0 * threshold;
+        assert!(km.sigma_xx_to_vff(cme).abs() < 1e-2 * km.sigma_xx_to_vv(cme));
+    }
 
-        let now = Instant::now();
-        let km = KineticMixing::new(1e3, 1e3, 1.0, 1e-3);
-        println!("{}, {}", km.relic_density(), now.elapsed().as_millis());
+    #[test]
+    fn test_cs_electroweak() {
+        let km = KineticMixing::new(1e3, 5e2, 1.0, 1e-3).unwrap();
+        let cme = 3e3;
+        let all = km.partial_annihilation_cross_section(cme, KineticMixingFinalStates::All);
+        for fs in [KineticMixingFinalStates::WW, KineticMixingFinalStates::VZ].iter() {
+            let sigma = km.partial_annihilation_cross_section(cme, *fs);
+            assert!(sigma.is_finite() && sigma >= 0.0 && sigma <= all);
+        }
+        // Above the W W threshold V decays into W bosons through its mixing
+        // with the Z.
+        assert!(km.width_v_to_ww() > 0.0);
 
-        let now = Instant::now();
-        let km = KineticMixing::new(1e3, 2e3, 1.0, 1e-3);
-        println!("{}, {}", km.relic_density(), now.elapsed().as_millis());
+        // The V Z mixing follows eps tan(theta_W) mz^2 / (mz^2 - mv^2) away
+        // from the Z-boson mass and is maximal, not divergent, on it.
+        let tan_w = km.sm.sin_theta_weak() / km.sm.cos_theta_weak();
+        let mz = km.sm.z_boson_mass;
+        let leading = 1e-3 * tan_w * mz.powi(2) / (mz.powi(2) - km.mv.powi(2));
+        assert!((km.vz_mixing_angle() / leading - 1.0).abs() < 1e-5);
+        let mut km_z = KineticMixing::new(1e3, mz, 1.0, 1e-3).unwrap();
+        let theta = km_z.vz_mixing_angle();
+        assert!((theta.abs() - std::f64::consts::FRAC_PI_4).abs() < 1e-3);
+        assert!(km_z.width_v_to_ww().is_finite());
+        km_z.set_eps(0.0).unwrap();
+        assert_eq!(km_z.vz_mixing_angle(), 0.0);
 
-        let now = Instant::now();
-        let km = KineticMixing::new(1e3, 4e3, 1.0, 1e-3);
-        println!("{}, {}", km.relic_density(), now.elapsed().as_millis());
+        // sigma(V Z) / theta_vz^2 for mx = 1000, mv = 500, gvxx = 1 and
+        // cme = 3000, from tests/reference/sigma_xx_to_v1v2.py.
+        let sigma = 2.441013025512463e-08;
+        let val = km.sigma_xx_to_vz(3e3) / km.vz_mixing_angle().powi(2);
+        assert!(((val - sigma) / sigma).abs() < 1e-11);
+    }
 
-        let now = Instant::now();
-        let km = KineticMixing::new(1e3, 1e4, 1.0, 1e-3);
-        println!("{}, {}", km.relic_density(), now.elapsed().as_millis());
+    #[test]
+    fn test_sommerfeld() {
+        let mut km = KineticMixing::new(1e3, 1.0, 0.5, 1e-3).unwrap();
+        for v in [1e-1, 1e-2, 1e-3].iter() {
+            let hulthen = km.sommerfeld_factor_hulthen(*v);
+            let numerical = km.sommerfeld_factor_numerical(*v);
+            assert!(hulthen >= 1.0 && numerical >= 1.0);
+        }
+        // Coulomb limit, S = 2 pi alpha / v / (1 - exp(-2 pi alpha / v)),
+        // the textbook result for an attractive Coulomb potential. The 1e-3
+        // tolerance covers the residual mass of the mediator.
+        km.set_mv(1e-9).unwrap();
+        let v = 1e-2;
+        let zeta = 2.0 * std::f64::consts::PI * km.alpha_dark() / v;
+        let coulomb = zeta / (1.0 - (-zeta).exp());
+        assert!((km.sommerfeld_factor_hulthen(v) / coulomb - 1.0).abs() < 1e-3);
+
+        km.set_mv(1.0).unwrap();
+        km.sommerfeld = Sommerfeld::Hulthen;
+        let sigmav = km.late_time_cross_section(7e-4);
+        let omega = km.relic_density().unwrap().omega;
+        assert!(sigmav.is_finite() && sigmav > 0.0);
+        assert!(omega.is_finite() && omega > 0.0);
+    }
+
+    #[test]
+    fn test_sommerfeld_numerical_limits() {
+        // Light mediators and slow dark matter used to need ~1e12 steps.
+        let km = KineticMixing::new(1e4, 1e-6, 0.5, 1e-3).unwrap();
+        let alpha = km.alpha_dark();
+        for v in [1.0, 1e-1, 1e-3, 1e-5, 1e-7, 1e-9].iter() {
+            let s = km.sommerfeld_factor_numerical(*v);
+            assert!(s.is_finite() && s >= 1.0);
+            let zeta = 2.0 * std::f64::consts::PI * alpha / v;
+            if *v >= 1e-3 {
+                assert!((s / (zeta / -(-zeta).exp_m1()) - 1.0).abs() < 1e-3);
+            }
+        }
+        // Just outside the Born limit the integration agrees with the first
+        // order Born approximation for the Yukawa potential,
+        // S = 1 + 2 / e_v atan(2 e_v / e_phi), up to terms of order (S - 1)^2.
+        let km = KineticMixing::new(1e3, 10.0, 0.5, 1e-3).unwrap();
+        let v = 2.0;
+        let ev = v / km.alpha_dark();
+        let ephi = 2.0 * km.mv() / (km.alpha_dark() * km.mx());
+        let born = 2.0 / ev * (2.0 * ev / ephi).atan();
+        assert!(born > 1e-2);
+        assert!((km.sommerfeld_factor_numerical(v) / (1.0 + born) - 1.0).abs() < born * born);
+    }
+
+    #[test]
+    fn test_sommerfeld_tabulated() {
+        let mut km = KineticMixing::new(1e3, 1.0, 0.5, 1e-3).unwrap();
+        km.sommerfeld = Sommerfeld::Numerical;
+        for &mv in [1.0, 3.0].iter() {
+            km.set_mv(mv).unwrap();
+            for v in [1.5, 0.3, 3e-2, 7e-3, 1e-3, 2e-4, 3e-5].iter() {
+                let direct = km.sommerfeld_factor_numerical(*v);
+                let cached = km.sommerfeld_factor(*v);
+                assert!((cached / direct - 1.0).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bound_states() {
+        let mut km = KineticMixing::new(1e4, 1.0, 1.5, 1e-3).unwrap();
+        km.sommerfeld = Sommerfeld::Hulthen;
+        assert!(km.has_bound_state(1));
+        for x in [20.0, 100.0, 1000.0].iter() {
+            let sigma = km.bound_state_cross_section(*x);
+            assert!(sigma.is_finite() && sigma >= 0.0);
+            assert!(km.ionization_rate(km.mx / x, 1) >= 0.0);
+        }
+        // Bound-state formation is an additional annihilation channel.
+        let omega = km.relic_density().unwrap().omega;
+        km.bound_states = true;
+        assert!(km.relic_density().unwrap().omega < omega);
+    }
+
+    #[test]
+    fn test_bound_states_closed() {
+        // Emitting V closes bound-state formation at large x when the binding
+        // energy is below mv, which must not spoil the error estimate.
+        let mut km = KineticMixing::new(1e4, 5e2, 1.5, 1e-3).unwrap();
+        km.bound_states = true;
+        assert!(km.has_bound_state(1) && km.binding_energy(1) < km.mv);
+        let x = 1e5;
+        assert_eq!(km.bound_state_cross_section_with_error(x), (0.0, 0.0));
+        assert!(km.try_thermal_cross_section(x).is_ok());
+    }
+
+    #[test]
+    fn test_rd_qcd_range() {
+        use crate::cosmology::Kination;
+        use crate::dof::{DegreesOfFreedom, DofBand, DofTable};
+        let mut km = KineticMixing::new(10.0, 1e2, 1.0, 1e-3).unwrap();
+        // The band is computed in the cosmology of the model.
+        km.cosmology = Arc::new(Kination::new(10.0));
+        let (lo, hi) = km.relic_density_qcd_range().unwrap();
+        km.cosmology = km.cosmology.with_dof(Arc::new(DofTable::Lattice.table()));
+        let omega = km.relic_density().unwrap().omega;
+        // chi freezes out at T ~ 0.5 GeV, inside the band of the lattice fit.
+        assert!(lo < omega && omega < hi);
+        assert!(hi / lo - 1.0 > 1e-3);
+
+        // A band of 5% around the lattice fit, as test input rather than a
+        // published error, widens the range around the central value.
+        let lattice = DofTable::Lattice.table();
+        let temps: Vec<f64> = (0..=80)
+            .map(|i| 10f64.powf(-3.0 + 0.05 * f64::from(i)))
+            .collect();
+        let geff: Vec<f64> = temps.iter().map(|&t| lattice.geff(t)).collect();
+        let heff: Vec<f64> = temps.iter().map(|&t| lattice.heff(t)).collect();
+        let geff_err: Vec<f64> = geff.iter().map(|g| 0.05 * g).collect();
+        let heff_err: Vec<f64> = heff.iter().map(|h| 0.05 * h).collect();
+        let band = DofBand::from_errors(&temps, &geff, &geff_err, &heff, &heff_err).unwrap();
+        let (lo, hi) = km.relic_density_dof_range(&[band]).unwrap();
+        assert!(lo < omega && omega < hi);
+    }
+
+    #[test]
+    fn test_rd_dark_sector_dof() {
+        use crate::dof::{DegreesOfFreedom, HaliaxDof};
+        let mut km = KineticMixing::new(1.0, 0.1, 1e-2, 1e-3).unwrap();
+        let res = km.relic_density().unwrap();
+        let omega = res.omega;
+        let dof = Arc::new(km.dark_sector_dof(Arc::new(HaliaxDof)));
+        // V and chi only ever add to the Standard Model degrees of freedom,
+        // by 3 + 7/8 4 once both are relativistic.
+        for temp in [1e-3, 1e-2, 0.1, 1.0, 10.0, 100.0].iter() {
+            let (heff, geff) = (HaliaxDof.heff(*temp), HaliaxDof.geff(*temp));
+            assert!(dof.heff(*temp) >= heff && dof.geff(*temp) >= geff);
+        }
+        assert!((dof.heff(100.0) - HaliaxDof.heff(100.0) - 6.5).abs() < 0.1);
+        // Omega h^2 ~ x_f / (sqrt(g_*) <sigma v>). At freeze-out V is
+        // semi-relativistic and raises sqrt(g_*) by a few percent, which
+        // lowers omega by at most that much; V becomes non-relativistic soon
+        // after, so the reduction is smaller but of the same order.
+        let tf = km.mx / res.xf;
+        let gain = dof.sqrt_gstar(tf) / HaliaxDof.sqrt_gstar(tf);
+        assert!(gain > 1.01);
+        km.cosmology = Arc::new(RadiationDomination::new(dof));
+        let omega_dark = km.relic_density().unwrap().omega;
+        let suppression = omega / omega_dark;
+        assert!(suppression > 1.0 + 0.25 * (gain - 1.0));
+        assert!(suppression < 1.02 * gain);
+    }
+
+    #[test]
+    fn test_relic_result() {
+        let km = KineticMixing::new(1e3, 1e2, 1.0, 1e-3).unwrap();
+        let res = km.relic_density().unwrap();
+        let total: f64 = res.channel_fractions.iter().map(|(_, f)| f).sum();
+        assert!((total - 1.0).abs() < 1e-10);
+        assert!(res.xf > 1.0 && res.xf < 100.0);
+        // Planck measures chi and chibar together.
+        assert_eq!(res.omega_total, 2.0 * res.omega);
+        assert_eq!(
+            res.planck_ratio,
+            res.omega_total / relic_result::PLANCK_OMEGA_H2
+        );
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert!(KineticMixing::new(0.0, 1e2, 1.0, 1e-3).is_err());
+        assert!(KineticMixing::new(1e3, -1e2, 1.0, 1e-3).is_err());
+        assert!(KineticMixing::new(1e3, 1e2, f64::NAN, 1e-3).is_err());
+        assert!(KineticMixing::new(1e3, 1e2, 1.0, 1.0).is_err());
+        let km = KineticMixing::new(1e3, 1e2, 1.0, 1e-3).unwrap();
+        assert!(km.try_thermal_cross_section(-1.0).is_err());
+        assert!(km.try_thermal_cross_section(10.0).is_ok());
+    }
+
+    #[test]
+    fn test_builder_and_setters() {
+        let mut km = KineticMixingBuilder::new(1e3, 1e2, 1.0, 1e-3)
+            .epsrel(1e-6)
+            .ode_reltol(1e-9)
+            .ode_abstol(1e-10)
+            .sommerfeld(Sommerfeld::Hulthen)
+            .build()
+            .unwrap();
+        assert_eq!((km.ode_reltol(), km.ode_abstol()), (1e-9, 1e-10));
+        assert!(km.set_ode_reltol(0.0).is_err());
+        km.set_ode_abstol(1e-8).unwrap();
+        assert_eq!(km.ode_abstol(), 1e-8);
+        km.set_mv(3e3).unwrap();
+        let fresh = KineticMixing::new(1e3, 3e3, 1.0, 1e-3).unwrap();
+        assert_eq!(km.widthv(), fresh.widthv());
+        assert!(km.set_eps(2.0).is_err());
+        assert_eq!(km.eps(), 1e-3);
+
+        km.set_width(Some(1.0)).unwrap();
+        km.set_mv(4e3).unwrap();
+        assert_eq!(km.widthv(), 1.0);
+        km.set_width(None).unwrap();
+        assert_eq!(km.widthv(), km.vm_decay_width());
+        assert!(KineticMixingBuilder::new(1e3, 1e2, 1.0, 1e-3)
+            .width(-1.0)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_sm_parameters() {
+        use crate::sm_parameters::{AlphaScheme, QuarkMassScheme};
+        let mut km = KineticMixing::new(1e3, 3e3, 1.0, 1e-3).unwrap();
+        let width = km.widthv();
+        let sm = SmParameters::default()
+            .with_alpha_scheme(AlphaScheme::ZPole)
+            .with_quark_mass_scheme(QuarkMassScheme::MsBar);
+        km.set_sm_parameters(sm).unwrap();
+        // Widths into light fermions scale with alpha_em.
+        assert!(km.widthv() > width);
+
+        let bad = SmParameters {
+            top_quark_mass: -1.0,
+            ..SmParameters::default()
+        };
+        assert!(km.set_sm_parameters(bad).is_err());
+        assert_eq!(km.sm(), &sm);
+    }
+
+    #[test]
+    fn test_running_couplings() {
+        use crate::sm_parameters::Quark;
+        let sm = SmParameters::default().with_running(true);
+        let mz = sm.z_boson_mass;
+        assert!((sm.alpha_s_at(mz) / sm.alpha_s_mz - 1.0).abs() < 1e-12);
+        assert!(sm.alpha_s_at(10.0) > sm.alpha_s_at(1e3));
+        assert!((1.0 / sm.alpha_em_at(mz) - 128.0).abs() < 2.0);
+        // The default bottom mass is mb(mb), so it is not converted again.
+        let mb = sm.bottom_quark_mass;
+        assert!((sm.quark_mass_at(Quark::Bottom, mb) / mb - 1.0).abs() < 1e-12);
+        // The top mass is a pole mass and is converted to mt(mt) < mt.
+        let mt = sm.top_quark_mass;
+        assert!(sm.quark_mass_at(Quark::Top, mt) < mt);
+        let mb = sm.quark_mass_at(Quark::Bottom, 1e3);
+        assert!(mb < sm.bottom_quark_mass);
+
+        let mut km = KineticMixing::new(1e3, 3e3, 1.0, 1e-3).unwrap();
+        let width = km.vm_partial_decay_width(KineticMixingFinalStates::BB);
+        let sigma = km.sigma_xx_to_qdqd(3e3, Quark::Bottom);
+        km.set_sm_parameters(sm).unwrap();
+        let width_running = km.vm_partial_decay_width(KineticMixingFinalStates::BB);
+        assert_eq!(width_running, km.width_v_to_qdqd(Quark::Bottom));
+        let sigma_running = km.sigma_xx_to_qdqd(3e3, Quark::Bottom);
+        assert!(width_running > width);
+        assert!(sigma_running > sigma);
+    }
+
+    #[test]
+    fn test_widths_match_cross_sections() {
+        use crate::sm_parameters::Quark;
+        // sigma(chi chibar -> f fbar) = gvxx^2 (s + 2 mx^2) Gamma(V* -> f fbar)
+        // / (sqrt(s - 4 mx^2) D(s)), with the width of an off-shell V of mass
+        // sqrt(s).
+        let km = KineticMixing::new(1e3, 5e2, 0.5, 1e-3).unwrap();
+        let cme: f64 = 3e3;
+        let s = cme * cme;
+        let pf = km.gvxx.powi(2) * (s + 2.0 * km.mx.powi(2))
+            / ((s - 4.0 * km.mx.powi(2)).sqrt() * km.propagator_denominator(s));
+        let pairs = [
+            (
+                km.sigma_xx_to_ququ(cme, Quark::Charm),
+                km.width_v_to_ququ_at(cme, Quark::Charm),
+            ),
+            (
+                km.sigma_xx_to_qdqd(cme, Quark::Bottom),
+                km.width_v_to_qdqd_at(cme, Quark::Bottom),
+            ),
+            (
+                km.sigma_xx_to_ll(cme, km.sm.muon_mass),
+                km.width_v_to_ll_at(cme, km.sm.muon_mass),
+            ),
+            (km.sigma_xx_to_nunu(cme), km.width_v_to_nunu_at(cme)),
+        ];
+        for &(sigma, width) in pairs.iter() {
+            assert!((sigma / (pf * width) - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_sigma_xx_to_vv_reference() {
+        // (mx, mv, gvxx, cme, sigma) with sigma from the closed form used before
+        // the cancellation-free rewrite, evaluated in 60-digit arithmetic by
+        // tests/reference/sigma_xx_to_vv.py.
+        let references = [
+            (1000.0, 100.0, 1.0, 3000.0, 1.205644932476223e-08),
+            (1000.0, 100.0, 1.0, 2000.0000002, 6.998288729779214e-04),
+            (100.0, 1000.0, 1.0, 2000.000001, 2.528885288712304e-12),
+            (1000.0, 999.9, 0.5, 2000.0000002, 4.971258550780128e-10),
+            (10000.0, 0.01, 1.0, 25000.0, 1.778308325962962e-10),
+            (1.0, 1e-06, 1.0, 1000.0, 5.099170427021839e-07),
+            (1000.0, 1.0, 0.1, 1000000.0, 5.099170427032039e-17),
+        ];
+        for &(mx, mv, gvxx, cme, sigma) in references.iter() {
+            let km = KineticMixing::new(mx, mv, gvxx, 1e-3).unwrap();
+            let val = km.sigma_xx_to_vv(cme);
+            assert!(((val - sigma) / sigma).abs() < 1e-11);
+        }
+
+        // No negative values close to the thresholds.
+        let km = KineticMixing::new(1e3, 1.0, 1.0, 1e-3).unwrap();
+        for i in 1..=15 {
+            let cme = 2e3 * (1.0 + 10f64.powi(-i));
+            assert!(km.sigma_xx_to_vv(cme) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_sigma_xx_to_v1v2_reference() {
+        // (cme, m1, m2, sigma) for mx = 1000 and gvxx = 1, with sigma from a
+        // 30-digit quadrature over the scattering angle of the spin-summed
+        // matrix element, built from explicit Dirac matrices by
+        // tests/reference/sigma_xx_to_v1v2.py.
+        let references = [
+            (2500.0, 100.0, 91.1876, 1.778911787497746e-08),
+            (3000.0, 900.0, 10.0, 1.264634565743899e-08),
+            (2000.5, 300.0, 50.0, 4.348574870804415e-07),
+            (2e5, 100.0, 1.0, 9.547860806989379e-12),
+        ];
+        let km = KineticMixing::new(1e3, 1e2, 1.0, 1e-3).unwrap();
+        for &(cme, m1, m2, sigma) in references.iter() {
+            for &val in [
+                km.sigma_xx_to_v1v2(cme, m1, m2),
+                km.sigma_xx_to_v1v2(cme, m2, m1),
+            ]
+            .iter()
+            {
+                assert!(((val - sigma) / sigma).abs() < 1e-11);
+            }
+        }
+    }
+
+    #[test]
+    fn test_solver_statistics() {
+        let km = KineticMixing::new(1e3, 1e2, 1.0, 1e-3).unwrap();
+        let (sol, stats) = km.solve_boltzmann_with_statistics();
+        assert!(stats.rhs_evaluations > 0 && stats.jacobian_evaluations > 0);
+        assert_eq!(stats.accepted_steps, sol.ts.len() - 1);
+        // Every Radau5 step evaluates the right-hand side at least once.
+        assert!(stats.rhs_evaluations >= stats.accepted_steps);
+        assert!(stats.thermal_averages >= stats.rhs_evaluations);
+        assert_eq!(stats.unconverged_averages, 0);
+        // The solve is deterministic, so computing the relic density repeats
+        // the same work.
+        let again = km.relic_density().unwrap().statistics;
+        assert_eq!(again.rhs_evaluations, stats.rhs_evaluations);
+        assert_eq!(again.jacobian_evaluations, stats.jacobian_evaluations);
+        assert_eq!(again.accepted_steps, stats.accepted_steps);
+        assert_eq!(again.thermal_averages, stats.thermal_averages);
+        assert_eq!(again.integrand_evaluations, stats.integrand_evaluations);
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<KineticMixing>();
+        assert_send_sync::<crate::reaction_network::ReactionNetwork>();
+        assert_send_sync::<RadiationDomination>();
+        assert_send_sync::<crate::cosmology::EquationOfState>();
+    }
+
+    #[test]
+    fn test_counters_not_shared_between_clones() {
+        let km = KineticMixing::new(1e3, 1e2, 1.0, 1e-3).unwrap();
+        km.thermal_cross_section(20.0);
+        let copy = km.clone();
+        copy.thermal_cross_section(20.0);
+        copy.thermal_cross_section(30.0);
+        let zero = std::time::Duration::from_secs(0);
+        assert_eq!(km.counters.statistics(0, zero).thermal_averages, 1);
+        assert_eq!(copy.counters.statistics(0, zero).thermal_averages, 2);
+        // Copies of a model holding the counters of a solve, as made by an
+        // integrator, keep counting into them.
+        let counters = Arc::new(instrumentation::Counters::default());
+        let mut solving = km.clone();
+        solving.counters = CounterHandle::shared(&counters);
+        solving.clone().thermal_cross_section(20.0);
+        assert_eq!(counters.statistics(0, zero).thermal_averages, 1);
+    }
+
+    #[test]
+    fn test_dense_output() {
+        let km = KineticMixing::new(1e3, 1e2, 1.0, 1e-3).unwrap();
+        let sol = km.dense_boltzmann_solution().unwrap();
+        let xs: Vec<f64> = (0..=30).map(|i| 10f64.powf(0.1 * f64::from(i))).collect();
+        let samples = sol.resample(&xs).unwrap();
+        // Abundance never increases in the absence of inverse decays.
+        assert!(samples.dy_dx.iter().all(|&d| d <= 0.0));
+        assert!(samples.y.windows(2).all(|w| w[1] <= w[0]));
+        let yinf = km.relic_density().unwrap().yinf;
+        assert!((sol.y(1e3).unwrap() / yinf - 1.0).abs() < 1e-10);
+        // Points outside of the solution are reported instead of extrapolated.
+        assert!(sol.y(1e4).is_err());
+        assert!(sol.dy_dx(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_boltz() {
+        let km = KineticMixing::new(1e3, 1e4, 1.0, 1e-1).unwrap();
+        let sol = km.solve_boltzmann();
+
+        // The solution is log(Y) as a function of log(x).
+        assert!(sol.ts.windows(2).all(|w| w[1] > w[0]));
+        assert!(sol.us.iter().all(|u| u[0].is_finite()));
+        assert!(sol.us[sol.us.len() - 1][0] < sol.us[0][0]);
+    }
+    #[test]
+    fn test_asymmetric() {
+        let km = KineticMixing::new(1e3, 1e4, 1.0, 1e-1).unwrap();
+        for eta in [1e-14, 1e-12].iter() {
+            // The symmetric component only ever shrinks relative to chi.
+            let rd = km.asymmetric_relic_density(*eta).unwrap();
+            assert!(rd.omega_asymmetric > 0.0);
+            assert!(rd.depletion > 0.0 && rd.depletion < 1.0);
+        }
+        // Without an asymmetry chi and chibar each freeze out like the single
+        // species of relic_density.
+        let rd = km.asymmetric_relic_density(0.0).unwrap();
+        let omega = km.relic_density().unwrap().omega;
+        assert_eq!(rd.omega_asymmetric, 0.0);
+        assert!((rd.depletion - 1.0).abs() < 1e-12);
+        assert!((rd.omega() / (2.0 * omega) - 1.0).abs() < 1e-2);
+        assert!(km.asymmetric_relic_density(-1e-12).is_err());
+    }
+    #[test]
+    fn test_rd_cosmology() {
+        use crate::cosmology::{EarlyMatterDomination, Kination};
+        let mut km = KineticMixing::new(1e3, 1e4, 1.0, 1e-1).unwrap();
+        let omega = km.relic_density().unwrap().omega;
+        // Freezing out at T ~ 50 while the extra component dominates, chi
+        // decouples earlier in the faster expansion and is left more abundant.
+        km.cosmology = Arc::new(Kination::new(10.0));
+        let omega_kination = km.relic_density().unwrap().omega;
+        assert!(omega_kination > 1.5 * omega);
+        km.cosmology = Arc::new(EarlyMatterDomination::new(1e3, 1.0));
+        let omega_emd = km.relic_density().unwrap().omega;
+        assert!(omega_emd > 1.5 * omega);
+    }
+    #[test]
+    fn test_rd_reheating() {
+        use super::reheating::{temperature_from_entropy_density, Reheating};
+        use crate::dof::DofTable;
+        let km = KineticMixing::new(1e3, 1e4, 1.0, 1e-1).unwrap();
+        let omega = |t_rh: f64, hubble_init: f64| {
+            let mut rh = Reheating::new(t_rh, 1e7, 0.0);
+            rh.hubble_init = hubble_init;
+            km.relic_density_reheating(&rh).unwrap().omega
+        };
+        // Freezing out during reheating, Omega grows roughly as T_RH^4.
+        let (low, high) = (omega(3.0, 1e10), omega(10.0, 1e10));
+        let power = (high / low).ln() / (10.0f64 / 3.0).ln();
+        assert!(power > 3.0 && power < 5.0);
+        // Reheating long before freeze-out leaves the standard relic density.
+        let rd = km.relic_density().unwrap().omega;
+        let late = omega(3e3, 10.0);
+        assert!((late / rd - 1.0).abs() < 0.1);
+
+        // The bath temperature is recovered from its entropy density, also on
+        // the step of a sharp QCD transition.
+        let cosmology = km
+            .cosmology
+            .with_dof(Arc::new(DofTable::ToySharpTransition150MeV.table()));
+        for temp in [1e-2, 0.1, 0.15, 0.2, 1.0, 1e3].iter() {
+            let s = cosmology.entropy_density(*temp);
+            let found = temperature_from_entropy_density(cosmology.as_ref(), s).unwrap();
+            assert!((found / temp - 1.0).abs() < 1e-9);
+        }
+        assert!(temperature_from_entropy_density(cosmology.as_ref(), f64::NAN).is_err());
+    }
+    #[test]
+    fn test_rd_dilution() {
+        use haliax_constants::prelude::M_PLANK;
+        // A mediator that decoupled while relativistic, at x = 1.
+        let km = KineticMixing::new(1e3, 1e2, 1.0, 1e-6).unwrap();
+        let rd = km
+            .relic_density_with_dilution(km.vm_equilibrium_abundance(1.0))
+            .unwrap();
+        // V decays before it can dominate, leaving the entropy nearly unchanged.
+        assert!((rd.dilution - 1.0).abs() < 0.05);
+        // For a small mixing V decays late, after dominating the energy
+        // density, and dilutes chi.
+        let km = KineticMixing::new(1e3, 1e2, 1.0, 1e-10).unwrap();
+        let rd = km
+            .relic_density_with_dilution(km.vm_equilibrium_abundance(1.0))
+            .unwrap();
+        assert!(rd.omega / rd.omega_undiluted < 1.0);
+        assert!(rd.dilution > 1.0);
+
+        // When V dominates long before it decays, the entropy grows by
+        // S_f / S_i = 1.83 g_*^(1/4) mv Y_v / (M_pl Gamma)^(1/2), Scherrer and
+        // Turner, Phys. Rev. D 31 (1985) 681. With Gamma = 1e-22 V decays at
+        // T ~ 15 MeV, where g_* is close to 10.75, after dominating from
+        // T ~ mv Y_v ~ 0.5 GeV.
+        let gamma = 1e-22;
+        let km = KineticMixingBuilder::new(1e3, 1e2, 1.0, 1e-3)
+            .width(gamma)
+            .build()
+            .unwrap();
+        let yv = 5e-3;
+        let sol = km.solve_dilution(0.0, yv).unwrap();
+        let dilution = sol.us[sol.us.len() - 1][1].exp();
+        let expected = 1.83 * 10.75f64.powf(0.25) * km.mv() * yv / (M_PLANK * gamma).sqrt();
+        assert!(expected > 10.0);
+        assert!((dilution / expected - 1.0).abs() < 0.15);
+
+        // A stable mediator never dilutes chi, and above 2 mx it decays into
+        // chi + chibar, which is not modelled.
+        let km = KineticMixing::new(1e3, 1e2, 1.0, 0.0).unwrap();
+        assert!(km.relic_density_with_dilution(1e-3).is_err());
+        let km = KineticMixing::new(1e3, 3e3, 1.0, 1e-6).unwrap();
+        assert!(km.relic_density_with_dilution(1e-3).is_err());
+        let km = KineticMixing::new(1e3, 1e2, 1.0, 1e-6).unwrap();
+        assert!(km.relic_density_with_dilution(0.0).is_err());
+    }
+    #[test]
+    fn test_rd_inverse_decays() {
+        let mut km = KineticMixing::new(1e3, 3e3, 1e-3, 1e-3).unwrap();
+        let omega = km.relic_density().unwrap().omega;
+        km.inverse_decays = true;
+        let omega_id = km.relic_density().unwrap().omega;
+        // For mv > 2 mx inverse decays keep chi in equilibrium for longer,
+        // during which it keeps annihilating.
+        assert!(omega_id < 0.9 * omega);
+        // Far above the resonance V is Boltzmann suppressed by exp(-mv / T),
+        // with mv / T >= 100 throughout the solve, and inverse decays change
+        // nothing beyond the tolerance of the ODE solver.
+        km.set_mv(1e5).unwrap();
+        let omega_id = km.relic_density().unwrap().omega;
+        km.inverse_decays = false;
+        let omega = km.relic_density().unwrap().omega;
+        assert!((omega_id / omega - 1.0).abs() < 1e-5);
+        km.inverse_decays = true;
+        // They are off below the threshold.
+        km.set_mv(1.5e3).unwrap();
+        let omega_id = km.relic_density().unwrap().omega;
+        km.inverse_decays = false;
+        assert_eq!(omega_id, km.relic_density().unwrap().omega);
+    }
+    #[test]
+    fn test_rd() {
+        for mv in [1e2, 1e3, 2e3, 4e3, 1e4].iter() {
+            let km = KineticMixing::new(1e3, *mv, 1.0, 1e-3).unwrap();
+            let omega = km.relic_density().unwrap().omega;
+            assert!(omega.is_finite() && omega > 0.0);
+        }
     }
 }
@@ -0,0 +1,146 @@
+use super::boltzmann::{X_END, X_START};
+use super::KineticMixing;
+use crate::error::{check_parameter, ModelError};
+use cyphus_diffeq::prelude::*;
+use haliax_constants::prelude::*;
+use ndarray::prelude::*;
+
+/// Final abundances of an asymmetric dark matter solve.
+#[derive(Clone, Debug)]
+pub struct AsymmetricRelicDensity {
+    /// Conserved asymmetry `eta = Y_chi - Y_chibar`.
+    pub eta: f64,
+    /// Final comoving abundance of chi.
+    pub ychi: f64,
+    /// Final comoving abundance of chibar.
+    pub ychibar: f64,
+    /// Relic density of the symmetric component, `2 Y_chibar`.
+    pub omega_symmetric: f64,
+    /// Relic density of the asymmetric component, `eta`.
+    pub omega_asymmetric: f64,
+    /// Depletion of the symmetric component, `r = Y_chibar / Y_chi`. Values
+    /// close to zero mean the symmetric part has been annihilated away.
+    pub depletion: f64,
+}
+
+impl AsymmetricRelicDensity {
+    /// Total relic density of chi and chibar.
+    pub fn omega(&self) -> f64 {
+        self.omega_symmetric + self.omega_asymmetric
+    }
+}
+
+impl KineticMixing {
+    fn dudt_asymmetric(&self, mut dw: ArrayViewMut1<f64>, w: ArrayView1<f64>, logx: f64, eta: f64) {
+        let x: f64 = logx.exp();
+        let temp: f64 = self.mx / x;
+        let s: f64 = self.cosmology.entropy_density(temp);
+        let n = self.neq(temp);
+        let weq: f64 = (n / s).ln();
+        let ychi = w[0].exp() + eta;
+
+        let pf: f64 = -s / self.cosmology.dlogx_dt(temp);
+        let sigmav: f64 = self.thermal_cross_section(x);
+        let rate: f64 = -pf * self.inverse_decay_rate(temp, s) / s;
+
+        // dW_chibar / dlogx
+        dw[0] = pf * sigmav * (ychi - (2.0 * weq - w[0]).exp());
+        // Long after freeze-out Y_chi exp(-2 weq) overflows while the rate
+        // underflows, so their product is formed in the exponent.
+        if rate > 0.0 {
+            let lr = rate.ln();
+            dw[0] += (lr - w[0]).exp() - (lr + ychi.ln() - 2.0 * weq).exp();
+        }
+    }
+    fn dfdu_asymmetric(&self, mut dw: ArrayViewMut2<f64>, w: ArrayView1<f64>, logx: f64) {
+        let x: f64 = logx.exp();
+        let temp: f64 = self.mx / x;
+        let s: f64 = self.cosmology.entropy_density(temp);
+        let n = self.neq(temp);
+        let weq: f64 = (n / s).ln();
+
+        let pf: f64 = -s / self.cosmology.dlogx_dt(temp);
+        let sigmav: f64 = self.thermal_cross_section(x);
+        let rate: f64 = -pf * self.inverse_decay_rate(temp, s) / s;
+
+        dw[[0, 0]] = pf * sigmav * (w[0].exp() + (2.0 * weq - w[0]).exp());
+        if rate > 0.0 {
+            let lr = rate.ln();
+            dw[[0, 0]] -= (lr - w[0]).exp() + (lr + w[0] - 2.0 * weq).exp();
+        }
+    }
+    /// Solve the coupled Boltzmann equations for `Y_chi` and `Y_chibar` given
+    /// a conserved asymmetry `eta = Y_chi - Y_chibar >= 0`. The solution
+    /// contains `[log(Y_chi), log(Y_chibar)]` as a function of `log(x)`.
+    ///
+    /// Only `Y_chibar` is integrated, with `Y_chi = Y_chibar + eta`, so the
+    /// asymmetry is conserved exactly. Both species start in chemical
+    /// equilibrium at `x = 1`, i.e. `Y_chi Y_chibar = Y_eq^2` with `Y_eq` the
+    /// equilibrium abundance of a single species at vanishing chemical
+    /// potential.
+    pub fn solve_boltzmann_asymmetric(&self, eta: f64) -> OdeSolution {
+        let x0: f64 = X_START;
+        let x1: f64 = X_END;
+        let temp = self.mx / x0;
+        let yeq = self.neq(temp) / self.cosmology.entropy_density(temp);
+        let ychibar = (0.25 * eta * eta + yeq * yeq).sqrt() - 0.5 * eta;
+        let uinit = array![ychibar.ln()];
+        let tspan = (x0.ln(), x1.ln());
+
+        let dudt = |mut dw: ArrayViewMut1<f64>,
+                    w: ArrayView1<f64>,
+                    logx: f64,
+                    p: &(KineticMixing, f64)| {
+            p.0.dudt_asymmetric(dw.view_mut(), w.view(), logx, p.1)
+        };
+        let dfdu = |mut dw: ArrayViewMut2<f64>,
+                    w: ArrayView1<f64>,
+                    logx: f64,
+                    p: &(KineticMixing, f64)| {
+            p.0.dfdu_asymmetric(dw.view_mut(), w.view(), logx)
+        };
+
+        let mut integrator =
+            OdeIntegratorBuilder::default(&dudt, uinit, tspan, Radau5, (self.clone(), eta))
+                .dfdu(&dfdu)
+                .reltol(self.ode_reltol)
+                .abstol(self.ode_abstol)
+                .build();
+
+        integrator.integrate();
+        let mut sol = integrator.sol;
+        sol.us = sol
+            .us
+            .iter()
+            .map(|u| array![(u[0].exp() + eta).ln(), u[0]])
+            .collect();
+        sol
+    }
+    /// Compute the symmetric and asymmetric relic densities for a conserved
+    /// asymmetry `eta = Y_chi - Y_chibar`.
+    ///
+    /// Unlike `relic_density`, which tracks a single species, both chi and
+    /// chibar are counted here. For `eta -> 0` the total is therefore twice
+    /// the result of `relic_density`.
+    ///
+    /// Fails if `eta` is negative, or like `relic_density` if the integration
+    /// stops early or the abundances become non-finite.
+    pub fn asymmetric_relic_density(&self, eta: f64) -> Result<AsymmetricRelicDensity, ModelError> {
+        check_parameter("eta", eta, "eta >= 0", |eta| eta >= 0.0)?;
+        let sol = self.solve_boltzmann_asymmetric(eta);
+        self.check_boltzmann_solution(&sol, X_END)?;
+        let u = &sol.us[sol.us.len() - 1];
+        let ychi = u[0].exp();
+        let ychibar = u[1].exp();
+        let pf = self.mx * S_TODAY / RHO_CRIT;
+
+        Ok(AsymmetricRelicDensity {
+            eta,
+            ychi,
+            ychibar,
+            omega_symmetric: 2.0 * ychibar * pf,
+            omega_asymmetric: eta * pf,
+            depletion: ychibar / ychi,
+        })
+    }
+}
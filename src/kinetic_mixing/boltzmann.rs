@@ -1,71 +1,195 @@
+use super::instrumentation::{CounterHandle, Counters, SolverStatistics};
+use super::relic_result::RelicResult;
 use super::KineticMixing;
+use crate::dof::{DegreesOfFreedom, DofBand, DofTable, ExtendedDof};
+use crate::error::{check_solution, ModelError};
+use crate::reaction_network::Species;
+use crate::statistics::{equilibrium_number_density, Statistics};
 use cyphus_diffeq::prelude::*;
 use cyphus_specfun::bessel::CylBesselK;
-use haliax_constants::prelude::*;
 use haliax_thermal_functions::prelude::*;
 use ndarray::prelude::*;
-use std::f64::consts::PI;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Range of `x = mx / T` over which the Boltzmann equation is solved.
+pub(super) const X_START: f64 = 1.0;
+pub(super) const X_END: f64 = 1000.0;
 
 impl KineticMixing {
-    fn dudt(&self, mut dw: ArrayViewMut1<f64>, w: ArrayView1<f64>, logx: f64) {
+    /// Compute the equilibrium number density of chi at `temp` using the
+    /// statistics selected through `statistics`.
+    pub fn neq(&self, temp: f64) -> f64 {
+        match self.statistics {
+            Statistics::MaxwellBoltzmann => neq(temp, self.mx, 2.0, 1),
+            stat => equilibrium_number_density(temp, self.mx, 2.0, stat),
+        }
+    }
+    /// Compute the rate per unit entropy density at which `V -> chi + chibar`
+    /// produces chi, `Gamma <K1/K2> n_v,eq / s`, with `V` in equilibrium. This
+    /// vanishes unless `inverse_decays` is enabled and `mv > 2 mx`.
+    pub(super) fn inverse_decay_rate(&self, temp: f64, s: f64) -> f64 {
+        if self.inverse_decays && self.mv > 2.0 * self.mx {
+            let z = self.mv / temp;
+            let nveq = equilibrium_number_density(temp, self.mv, 3.0, Statistics::MaxwellBoltzmann);
+            self.width_v_to_xx() * z.cyl_bessel_k1_scaled() / z.cyl_bessel_kn_scaled(2) * nveq / s
+        } else {
+            0.0
+        }
+    }
+    pub(super) fn dudt(&self, mut dw: ArrayViewMut1<f64>, w: ArrayView1<f64>, logx: f64) {
         let x: f64 = logx.exp();
         let temp: f64 = self.mx / x;
-        let s: f64 = sm_entropy_density(temp);
-        let n = neq(temp, self.mx, 2.0, 1);
+        let s: f64 = self.cosmology.entropy_density(temp);
+        let n = self.neq(temp);
         let weq: f64 = (n / s).ln();
         let ww: f64 = w[0];
 
-        let pf: f64 = -(std::f64::consts::PI / 45.0).sqrt() * M_PLANK * sm_sqrt_gstar(temp) * temp;
+        let pf: f64 = -s / self.cosmology.dlogx_dt(temp);
         let sigmav: f64 = self.thermal_cross_section(x);
+        let rate: f64 = -pf * self.inverse_decay_rate(temp, s) / s;
 
         // dW_e / dlogx
         dw[0] = pf * sigmav * (ww.exp() - (2.0 * weq - ww).exp());
+        // Long after freeze-out exp(ww - 2 weq) overflows while the rate
+        // underflows, so their product is formed in the exponent.
+        if rate > 0.0 {
+            let lr = rate.ln();
+            dw[0] += (lr - ww).exp() - (lr + ww - 2.0 * weq).exp();
+        }
     }
     fn dfdu(&self, mut dw: ArrayViewMut2<f64>, w: ArrayView1<f64>, logx: f64) {
         let x: f64 = logx.exp();
         let temp: f64 = self.mx / x;
-        let s: f64 = sm_entropy_density(temp);
+        let s: f64 = self.cosmology.entropy_density(temp);
 
-        let n = neq(temp, self.mx, 2.0, 1);
+        let n = self.neq(temp);
         let weq: f64 = (n / s).ln();
         let ww: f64 = w[0];
 
-        let pf: f64 = -(std::f64::consts::PI / 45.0).sqrt() * M_PLANK * sm_sqrt_gstar(temp) * temp;
+        let pf: f64 = -s / self.cosmology.dlogx_dt(temp);
         let sigmav: f64 = self.thermal_cross_section(x);
+        let rate: f64 = -pf * self.inverse_decay_rate(temp, s) / s;
 
         // dW_e / dlogx
         dw[[0, 0]] = pf * sigmav * (ww.exp() + (2.0 * weq - ww).exp());
+        if rate > 0.0 {
+            let lr = rate.ln();
+            dw[[0, 0]] -= (lr - ww).exp() + (lr + ww - 2.0 * weq).exp();
+        }
     }
     pub fn solve_boltzmann(&self) -> OdeSolution {
-        let x0: f64 = 1.0;
-        let x1: f64 = 1000.0;
+        self.solve_boltzmann_with_statistics().0
+    }
+    /// Solve the Boltzmann equation and report the work done by the
+    /// integrators. Thermal averages computed through this model outside of
+    /// the solver are not counted.
+    pub fn solve_boltzmann_with_statistics(&self) -> (OdeSolution, SolverStatistics) {
+        let start = Instant::now();
+        let counters = Arc::new(Counters::default());
+        let mut km = self.clone();
+        km.counters = CounterHandle::shared(&counters);
+
+        let x0: f64 = X_START;
+        let x1: f64 = X_END;
         let temp = self.mx / x0;
-        let n = neq(temp, self.mx, 2.0, 1);
-        let uinit = array![(n / sm_entropy_density(temp)).ln()];
+        let n = self.neq(temp);
+        let uinit = array![(n / self.cosmology.entropy_density(temp)).ln()];
         let tspan = (x0.ln(), x1.ln());
 
-        let dudt = |mut dw: ArrayViewMut1<f64>,
-                    w: ArrayView1<f64>,
-                    logx: f64,
-                    p: &KineticMixing| { p.dudt(dw.view_mut(), w.view(), logx) };
-        let dfdu = |mut dw: ArrayViewMut2<f64>,
-                    w: ArrayView1<f64>,
-                    logx: f64,
-                    p: &KineticMixing| { p.dfdu(dw.view_mut(), w.view(), logx) };
+        let dudt =
+            |mut dw: ArrayViewMut1<f64>, w: ArrayView1<f64>, logx: f64, p: &KineticMixing| {
+                p.counters.count_rhs();
+                p.dudt(dw.view_mut(), w.view(), logx)
+            };
+        let dfdu =
+            |mut dw: ArrayViewMut2<f64>, w: ArrayView1<f64>, logx: f64, p: &KineticMixing| {
+                p.counters.count_jacobian();
+                p.dfdu(dw.view_mut(), w.view(), logx)
+            };
 
-        let mut integrator =
-            OdeIntegratorBuilder::default(&dudt, uinit, tspan, Radau5, self.clone())
-                .dfdu(&dfdu)
-                .reltol(1e-7)
-                .abstol(1e-7)
-                .build();
+        let mut integrator = OdeIntegratorBuilder::default(&dudt, uinit, tspan, Radau5, km)
+            .dfdu(&dfdu)
+            .reltol(self.ode_reltol)
+            .abstol(self.ode_abstol)
+            .build();
 
         integrator.integrate();
-        integrator.sol
+        let sol = integrator.sol;
+        let stats = counters.statistics(sol.ts.len().saturating_sub(1), start.elapsed());
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            mx = self.mx,
+            mv = self.mv,
+            gvxx = self.gvxx,
+            eps = self.eps,
+            ?stats,
+            "solved Boltzmann equation"
+        );
+        (sol, stats)
+    }
+    /// Compute the relic density of chi together with the freeze-out point,
+    /// the channel fractions and the trajectory of the abundance.
+    ///
+    /// Fails if the integration stops early or the abundance becomes
+    /// non-finite. In the latter case a failing thermal average at that
+    /// point is reported if there is one.
+    pub fn relic_density(&self) -> Result<RelicResult, ModelError> {
+        let (sol, stats) = self.solve_boltzmann_with_statistics();
+        self.check_boltzmann_solution(&sol, X_END)?;
+        Ok(self.relic_result(&sol, stats))
+    }
+    /// Check that a solution of a Boltzmann equation in `log(x)` is finite
+    /// and reaches `x_end`. If the abundance becomes non-finite, a failing
+    /// thermal average at that point is reported if there is one.
+    pub(super) fn check_boltzmann_solution(
+        &self,
+        sol: &OdeSolution,
+        x_end: f64,
+    ) -> Result<(), ModelError> {
+        match check_solution(sol, x_end.ln(), x_end, |logx, _| logx.exp()) {
+            Err(ModelError::NonFiniteAbundance { x }) => {
+                self.try_thermal_cross_section(x)?;
+                Err(ModelError::NonFiniteAbundance { x })
+            }
+            res => res,
+        }
+    }
+    /// Compute the degrees of freedom of the bath `base` with `V` and chi added,
+    /// for a dark sector in equilibrium with the Standard Model. Using these in
+    /// the cosmology includes the dark sector in `H(T)` and `s(T)`, e.g.
+    /// `RadiationDomination::new(Arc::new(km.dark_sector_dof(Arc::new(HaliaxDof))))`.
+    pub fn dark_sector_dof(&self, base: Arc<dyn DegreesOfFreedom + Send + Sync>) -> ExtendedDof {
+        let species = vec![
+            Species::new("V", self.mv, 3.0, Statistics::BoseEinstein),
+            Species::new("chi", self.mx, 4.0, Statistics::FermiDirac),
+        ];
+        ExtendedDof::new(base, species)
+    }
+    /// Compute the smallest and largest relic density obtained with the
+    /// literature tables of the degrees of freedom, `DofTable::LITERATURE`,
+    /// sweeping each across its uncertainty band. The toy models with a sharp
+    /// QCD transition are not included. Only the degrees of freedom of the
+    /// cosmology are replaced; its expansion history is kept.
+    pub fn relic_density_qcd_range(&self) -> Result<(f64, f64), ModelError> {
+        let bands: Vec<DofBand> = DofTable::LITERATURE.iter().map(|t| t.band()).collect();
+        self.relic_density_dof_range(&bands)
     }
-    pub fn relic_density(&self) -> f64 {
-        let sol = self.solve_boltzmann();
-        let yinf = sol.us.last().unwrap()[0].exp();
-        yinf * self.mx * S_TODAY / RHO_CRIT
+    /// Compute the smallest and largest relic density obtained with the
+    /// central values and both edges of each of `bands`, e.g. published
+    /// lattice tables loaded with `DofBand::from_errors`. The edges of a band
+    /// shift `g_eff` and `h_eff` together.
+    pub fn relic_density_dof_range(&self, bands: &[DofBand]) -> Result<(f64, f64), ModelError> {
+        let mut km = self.clone();
+        let (mut lo, mut hi) = (f64::INFINITY, f64::NEG_INFINITY);
+        for band in bands.iter() {
+            for table in band.tables().iter() {
+                km.cosmology = self.cosmology.with_dof(Arc::new((*table).clone()));
+                let rd = km.relic_density()?.omega;
+                lo = lo.min(rd);
+                hi = hi.max(rd);
+            }
+        }
+        Ok((lo, hi))
     }
 }
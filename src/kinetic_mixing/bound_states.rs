@@ -0,0 +1,157 @@
+use super::KineticMixing;
+use cyphus_integration::prelude::*;
+use std::f64::consts::PI;
+
+/// A Yukawa potential supports the level `n` roughly when
+/// `alpha mu / mv > YUKAWA_CRITICAL n^2`, with `mu = mx / 2`.
+const YUKAWA_CRITICAL: f64 = 0.84;
+
+impl KineticMixing {
+    /// Compute the binding energy `alpha^2 mu / (2 n^2)` of the level `n` of
+    /// darkonium in the Coulomb approximation.
+    pub fn binding_energy(&self, n: u32) -> f64 {
+        self.alpha_dark().powi(2) * self.mx / (4.0 * f64::from(n * n))
+    }
+    /// Check whether the Yukawa potential from `V` exchange supports the
+    /// level `n`.
+    pub fn has_bound_state(&self, n: u32) -> bool {
+        self.alpha_dark() * self.mx / (2.0 * self.mv) > YUKAWA_CRITICAL * f64::from(n * n)
+    }
+    /// Compute `sigma v` for chi + chibar -> B_n + V at relative velocity `v`,
+    /// summed over the spin and angular-momentum states of the level `n`.
+    ///
+    /// The ground state uses the Coulomb result
+    /// `(pi alpha^2 / mx^2) (2^9 / 3) zeta^4 exp(-4 zeta arccot(zeta)) / (1 + zeta^2)^2 S(zeta)`
+    /// with `zeta = alpha / v` and `S` the Coulomb Sommerfeld factor. Excited
+    /// levels are obtained from the ground state using Kramers' scaling. The
+    /// emission of a massive `V` is suppressed by its phase space.
+    pub fn bound_state_formation_sigma_v(&self, v: f64, n: u32) -> f64 {
+        if !self.has_bound_state(n) {
+            return 0.0;
+        }
+        let alpha = self.alpha_dark();
+        let ekin = 0.25 * self.mx * v * v;
+        let omega = ekin + self.binding_energy(n);
+        if omega <= self.mv {
+            return 0.0;
+        }
+
+        let zeta = alpha / v;
+        let sommerfeld = 2.0 * PI * zeta / -(-2.0 * PI * zeta).exp_m1();
+        let ground = PI * alpha * alpha / (self.mx * self.mx) * 512.0 / 3.0
+            * zeta.powi(4)
+            * (-4.0 * zeta * (1.0 / zeta).atan()).exp()
+            / (1.0 + zeta * zeta).powi(2)
+            * sommerfeld;
+
+        // sigma_n / sigma_1 = (E + I) / (n^3 (E + I / n^2))
+        let nf = f64::from(n);
+        let kramers = (ekin + self.binding_energy(1)) / (nf.powi(3) * omega);
+        let r = (self.mv / omega).powi(2);
+        ground * kramers * (1.0 - r).sqrt() * (1.0 + 0.5 * r)
+    }
+    /// Compute the thermally averaged bound-state formation cross section into
+    /// the level `n` at `x = mx / temperature`.
+    pub fn thermal_bound_state_formation(&self, x: f64, n: u32) -> f64 {
+        self.thermal_bound_state_formation_with_error(x, n).val
+    }
+    fn thermal_bound_state_formation_with_error(&self, x: f64, n: u32) -> IntegrationResult {
+        // The relative velocity has a one-dimensional dispersion T / mu = 2 / x.
+        self.maxwellian_average_with_error(
+            |v| self.bound_state_formation_sigma_v(v, n),
+            (2.0 / x).sqrt(),
+        )
+    }
+    /// Compute the rate at which a bound state in the level `n` is ionised by
+    /// the dark bath, which is taken to share the temperature `temp` of the
+    /// Standard Model bath. It follows from detailed balance,
+    /// `Gamma_ion = <sigma v> (g_chi^2 / g_B) (mu T / 2 pi)^(3/2) exp(-E_B / T)`.
+    pub fn ionization_rate(&self, temp: f64, n: u32) -> f64 {
+        let sigmav = self.thermal_bound_state_formation(self.mx / temp, n);
+        self.ionization_rate_from(sigmav, temp, n)
+    }
+    /// Compute the ionisation rate of the level `n` from its thermally
+    /// averaged formation cross section `sigmav` at `temp`.
+    fn ionization_rate_from(&self, sigmav: f64, temp: f64, n: u32) -> f64 {
+        // The level n has g_B = 4 n^2 states.
+        let ratio = 1.0 / f64::from(n * n);
+        sigmav
+            * ratio
+            * (self.mx * temp / (4.0 * PI)).powf(1.5)
+            * (-self.binding_energy(n) / temp).exp()
+    }
+    /// Compute the width of the spin-singlet ground state, B -> V V.
+    pub fn para_darkonium_width(&self) -> f64 {
+        0.5 * self.alpha_dark().powi(5) * self.mx
+    }
+    /// Compute the width of the spin-triplet ground state, B -> V V V.
+    pub fn ortho_darkonium_width(&self) -> f64 {
+        2.0 * (PI * PI - 9.0) * self.alpha_dark().powi(6) * self.mx / (9.0 * PI)
+    }
+    /// Compute the rate at which the first excited level decays into the
+    /// ground state. All `n = 2` states are treated as `2p`, with the rate
+    /// `(2/3)^8 alpha^5 mu`, and the transition is closed if `V` is heavier than
+    /// the energy difference.
+    fn deexcitation_rate(&self) -> f64 {
+        if self.mv >= self.binding_energy(1) - self.binding_energy(2) {
+            return 0.0;
+        }
+        (2.0f64 / 3.0).powi(8) * self.alpha_dark().powi(5) * 0.5 * self.mx
+    }
+    /// Compute the effective annihilation cross section from bound-state
+    /// formation at `x = mx / temperature`.
+    ///
+    /// Each bound state is either ionised or destroyed, the ground state by
+    /// decaying and the first excited level by decaying into the ground state.
+    /// The contribution of each level is its formation cross section times the
+    /// probability that it is destroyed before being ionised. The ground
+    /// state is formed as para- and ortho-darkonium in the ratio 1 : 3.
+    pub fn bound_state_cross_section(&self, x: f64) -> f64 {
+        self.bound_state_cross_section_with_error(x).0
+    }
+    /// Compute the effective bound-state cross section like
+    /// `bound_state_cross_section`, together with an estimate of its absolute
+    /// error from the thermal averages of the formation cross sections.
+    ///
+    /// The ionisation rate of a level is proportional to its formation cross
+    /// section, so the probability of it being destroyed decreases as the
+    /// cross section increases. The error of the contribution of a level is
+    /// therefore at most that of its formation cross section times that
+    /// probability, plus, for the excited level, the relative error of the
+    /// formation of the ground state times its contribution.
+    pub(super) fn bound_state_cross_section_with_error(&self, x: f64) -> (f64, f64) {
+        if !self.has_bound_state(1) {
+            return (0.0, 0.0);
+        }
+        let temp = self.mx / x;
+        let formation1 = self.thermal_bound_state_formation_with_error(x, 1);
+        let ion1 = self.ionization_rate_from(formation1.val, temp, 1);
+        let para = self.para_darkonium_width();
+        let ortho = self.ortho_darkonium_width();
+        let r1 = 0.25 * para / (para + ion1) + 0.75 * ortho / (ortho + ion1);
+        let mut sigmav = formation1.val * r1;
+        let mut err = formation1.err.abs() * r1;
+
+        let deex = self.deexcitation_rate();
+        if self.has_bound_state(2) && deex > 0.0 {
+            let formation2 = self.thermal_bound_state_formation_with_error(x, 2);
+            let ion2 = self.ionization_rate_from(formation2.val, temp, 2);
+            let r2 = deex / (deex + ion2) * r1;
+            let sigmav2 = formation2.val * r2;
+            sigmav += sigmav2;
+            err += formation2.err.abs() * r2 + relative_error(&formation1) * sigmav2;
+        }
+        (sigmav, err)
+    }
+}
+
+/// Compute the relative error of an integral, which is zero if the integral
+/// vanishes, e.g. because bound-state formation is closed over the whole
+/// range of velocities.
+fn relative_error(result: &IntegrationResult) -> f64 {
+    if result.val == 0.0 {
+        0.0
+    } else {
+        (result.err / result.val).abs()
+    }
+}
@@ -0,0 +1,260 @@
+use super::instrumentation::CounterHandle;
+use super::propagator::WidthScheme;
+use super::sommerfeld::{Sommerfeld, SommerfeldCache};
+use super::KineticMixing;
+use crate::cosmology::{Cosmology, RadiationDomination};
+use crate::error::{check_parameter, ModelError};
+use crate::sm_parameters::SmParameters;
+use crate::statistics::Statistics;
+use std::sync::Arc;
+
+/// Builder for `KineticMixing` which validates the parameters and computes
+/// the width of `V` and the thermal-average breakpoints from them.
+#[derive(Clone)]
+pub struct KineticMixingBuilder {
+    mx: f64,
+    mv: f64,
+    gvxx: f64,
+    eps: f64,
+    width: Option<f64>,
+    sm: SmParameters,
+    epsrel: f64,
+    ode_reltol: f64,
+    ode_abstol: f64,
+    cosmology: Arc<dyn Cosmology + Send + Sync>,
+    inverse_decays: bool,
+    statistics: Statistics,
+    width_scheme: WidthScheme,
+    sommerfeld: Sommerfeld,
+    bound_states: bool,
+}
+
+impl KineticMixingBuilder {
+    /// Start building a model with dark matter mass `mx`, mediator mass `mv`,
+    /// dark coupling `gvxx` and kinetic-mixing parameter `eps`.
+    pub fn new(mx: f64, mv: f64, gvxx: f64, eps: f64) -> KineticMixingBuilder {
+        KineticMixingBuilder {
+            mx,
+            mv,
+            gvxx,
+            eps,
+            width: None,
+            sm: SmParameters::default(),
+            epsrel: 1e-8,
+            ode_reltol: 1e-7,
+            ode_abstol: 1e-7,
+            cosmology: Arc::new(RadiationDomination::default()),
+            inverse_decays: false,
+            statistics: Statistics::MaxwellBoltzmann,
+            width_scheme: WidthScheme::Fixed,
+            sommerfeld: Sommerfeld::Off,
+            bound_states: false,
+        }
+    }
+    /// Fix the width of `V` instead of computing it from the couplings.
+    pub fn width(mut self, width: f64) -> Self {
+        self.width = Some(width);
+        self
+    }
+    /// Standard Model inputs used in the couplings, widths and thresholds.
+    pub fn sm_parameters(mut self, sm: SmParameters) -> Self {
+        self.sm = sm;
+        self
+    }
+    /// Relative tolerance of the thermal averages.
+    pub fn epsrel(mut self, epsrel: f64) -> Self {
+        self.epsrel = epsrel;
+        self
+    }
+    /// Relative tolerance used when solving the Boltzmann equation and the
+    /// other evolution equations of the model.
+    pub fn ode_reltol(mut self, reltol: f64) -> Self {
+        self.ode_reltol = reltol;
+        self
+    }
+    /// Absolute tolerance used when solving the Boltzmann equation and the
+    /// other evolution equations of the model.
+    pub fn ode_abstol(mut self, abstol: f64) -> Self {
+        self.ode_abstol = abstol;
+        self
+    }
+    pub fn cosmology(mut self, cosmology: Arc<dyn Cosmology + Send + Sync>) -> Self {
+        self.cosmology = cosmology;
+        self
+    }
+    pub fn inverse_decays(mut self, inverse_decays: bool) -> Self {
+        self.inverse_decays = inverse_decays;
+        self
+    }
+    pub fn statistics(mut self, statistics: Statistics) -> Self {
+        self.statistics = statistics;
+        self
+    }
+    pub fn width_scheme(mut self, width_scheme: WidthScheme) -> Self {
+        self.width_scheme = width_scheme;
+        self
+    }
+    pub fn sommerfeld(mut self, sommerfeld: Sommerfeld) -> Self {
+        self.sommerfeld = sommerfeld;
+        self
+    }
+    pub fn bound_states(mut self, bound_states: bool) -> Self {
+        self.bound_states = bound_states;
+        self
+    }
+    /// Validate the parameters and build the model.
+    pub fn build(self) -> Result<KineticMixing, ModelError> {
+        check_masses_and_couplings(self.mx, self.mv, self.gvxx, self.eps)?;
+        if let Some(width) = self.width {
+            check_width(width)?;
+        }
+        self.sm.validate()?;
+        check_tolerance("epsrel", self.epsrel)?;
+        check_tolerance("ode_reltol", self.ode_reltol)?;
+        check_tolerance("ode_abstol", self.ode_abstol)?;
+
+        let mut km = KineticMixing {
+            mx: self.mx,
+            mv: self.mv,
+            gvxx: self.gvxx,
+            eps: self.eps,
+            widthv: 0.0,
+            width_override: self.width,
+            running_width_scale: Some(1.0),
+            sm: self.sm,
+            cosmology: self.cosmology,
+            inverse_decays: self.inverse_decays,
+            statistics: self.statistics,
+            width_scheme: self.width_scheme,
+            sommerfeld: self.sommerfeld,
+            bound_states: self.bound_states,
+            epsrel: self.epsrel,
+            ode_reltol: self.ode_reltol,
+            ode_abstol: self.ode_abstol,
+            counters: CounterHandle::default(),
+            singular_points: vec![],
+            sommerfeld_table: SommerfeldCache::default(),
+        };
+        km.update_derived();
+        Ok(km)
+    }
+}
+
+fn check_masses_and_couplings(mx: f64, mv: f64, gvxx: f64, eps: f64) -> Result<(), ModelError> {
+    check_parameter("mx", mx, "mx > 0", |m| m > 0.0)?;
+    check_parameter("mv", mv, "mv > 0", |m| m > 0.0)?;
+    check_parameter("gvxx", gvxx, "gvxx >= 0", |g| g >= 0.0)?;
+    check_parameter("eps", eps, "0 <= eps < 1", |e| (0.0..1.0).contains(&e))
+}
+
+fn check_width(width: f64) -> Result<(), ModelError> {
+    check_parameter("widthv", width, "widthv >= 0", |w| w >= 0.0)
+}
+
+fn check_tolerance(name: &'static str, tol: f64) -> Result<(), ModelError> {
+    check_parameter(name, tol, "a positive tolerance", |t| t > 0.0)
+}
+
+impl KineticMixing {
+    pub fn mx(&self) -> f64 {
+        self.mx
+    }
+    pub fn mv(&self) -> f64 {
+        self.mv
+    }
+    pub fn gvxx(&self) -> f64 {
+        self.gvxx
+    }
+    pub fn eps(&self) -> f64 {
+        self.eps
+    }
+    pub fn sm(&self) -> &SmParameters {
+        &self.sm
+    }
+    /// Width of `V`, either computed from the couplings or fixed by the user.
+    pub fn widthv(&self) -> f64 {
+        self.widthv
+    }
+    /// Recompute the width of `V` and the thermal-average breakpoints, and
+    /// reset the table of the Sommerfeld factor, after a parameter change.
+    fn update_derived(&mut self) {
+        let width = self.vm_decay_width();
+        self.widthv = self.width_override.unwrap_or(width);
+        self.running_width_scale = match self.width_override {
+            None => Some(1.0),
+            Some(_) if width > 0.0 => Some(self.widthv / width),
+            Some(_) => None,
+        };
+        self.singular_points = self.compute_singular_points();
+        self.sommerfeld_table = SommerfeldCache::default();
+    }
+    pub fn set_mx(&mut self, mx: f64) -> Result<(), ModelError> {
+        check_masses_and_couplings(mx, self.mv, self.gvxx, self.eps)?;
+        self.mx = mx;
+        self.update_derived();
+        Ok(())
+    }
+    pub fn set_mv(&mut self, mv: f64) -> Result<(), ModelError> {
+        check_masses_and_couplings(self.mx, mv, self.gvxx, self.eps)?;
+        self.mv = mv;
+        self.update_derived();
+        Ok(())
+    }
+    pub fn set_gvxx(&mut self, gvxx: f64) -> Result<(), ModelError> {
+        check_masses_and_couplings(self.mx, self.mv, gvxx, self.eps)?;
+        self.gvxx = gvxx;
+        self.update_derived();
+        Ok(())
+    }
+    pub fn set_eps(&mut self, eps: f64) -> Result<(), ModelError> {
+        check_masses_and_couplings(self.mx, self.mv, self.gvxx, eps)?;
+        self.eps = eps;
+        self.update_derived();
+        Ok(())
+    }
+    /// Fix the width of `V`, or compute it from the couplings if `width` is
+    /// `None`. A fixed width is kept when other parameters change.
+    pub fn set_width(&mut self, width: Option<f64>) -> Result<(), ModelError> {
+        if let Some(w) = width {
+            check_width(w)?;
+        }
+        self.width_override = width;
+        self.update_derived();
+        Ok(())
+    }
+    /// Replace the Standard Model inputs.
+    pub fn set_sm_parameters(&mut self, sm: SmParameters) -> Result<(), ModelError> {
+        sm.validate()?;
+        self.sm = sm;
+        self.update_derived();
+        Ok(())
+    }
+    /// Set the relative tolerance of the thermal averages.
+    pub fn set_epsrel(&mut self, epsrel: f64) -> Result<(), ModelError> {
+        check_tolerance("epsrel", epsrel)?;
+        self.epsrel = epsrel;
+        Ok(())
+    }
+    /// Relative tolerance used when solving the Boltzmann equation and the
+    /// other evolution equations of the model.
+    pub fn ode_reltol(&self) -> f64 {
+        self.ode_reltol
+    }
+    /// Absolute tolerance used when solving the Boltzmann equation and the
+    /// other evolution equations of the model.
+    pub fn ode_abstol(&self) -> f64 {
+        self.ode_abstol
+    }
+    /// Set the relative tolerance of the evolution equations.
+    pub fn set_ode_reltol(&mut self, reltol: f64) -> Result<(), ModelError> {
+        check_tolerance("ode_reltol", reltol)?;
+        self.ode_reltol = reltol;
+        Ok(())
+    }
+    /// Set the absolute tolerance of the evolution equations.
+    pub fn set_ode_abstol(&mut self, abstol: f64) -> Result<(), ModelError> {
+        check_tolerance("ode_abstol", abstol)?;
+        self.ode_abstol = abstol;
+        Ok(())
+    }
+}
@@ -1,47 +1,68 @@
 use super::{KineticMixing, KineticMixingFinalStates};
-use haliax_constants::prelude::*;
+use crate::sm_parameters::Quark;
+use cyphus_integration::prelude::*;
+use lazy_static::lazy_static;
+
+/// Relative tolerance of the integral over the mass of the off-shell mediator
+/// in `sigma_xx_to_vff`.
+const VFF_EPSREL: f64 = 1e-6;
+
+lazy_static! {
+    /// Integrator over the mass of the off-shell mediator in `sigma_xx_to_vff`
+    /// when its peak is out of range.
+    static ref VFF_INTEGRATOR: GaussKronrodIntegrator = GaussKronrodIntegratorBuilder::default()
+        .epsabs(0.0)
+        .epsrel(VFF_EPSREL)
+        .limit(200)
+        .build();
+}
 
 impl KineticMixing {
     /// Compute the annihilation cross-section for dark matter to up-type quarks.
-    pub fn sigma_xx_to_ququ(&self, cme: f64, mf: f64) -> f64 {
-        if cme > 2.0 * self.mx && cme > 2.0 * mf {
+    /// With running couplings the running mass of `quark`, `alpha_em` and
+    /// the QCD correction at `cme` are used.
+    pub fn sigma_xx_to_ququ(&self, cme: f64, quark: Quark) -> f64 {
+        if cme > 2.0 * self.mx && cme > 2.0 * self.sm.quark_mass(quark) {
             let temp1: f64 = self.mx.powi(2);
             let temp2: f64 = cme.powi(2);
-            let temp3: f64 = mf.powi(2);
-            let temp4: f64 = self.mv.powi(2);
-            (ALPHA_EM
+            let mq = self.sm.quark_mass_at(quark, cme);
+            let temp3: f64 = mq.powi(2);
+            (self.sm.alpha_em_at(cme)
+                * self.sm.qcd_correction(cme)
                 * self.eps.powi(2)
                 * self.gvxx.powi(2)
                 * (2.0 * temp1 + temp2)
-                * (temp2 - 4.0 * temp3).sqrt()
+                * threshold_factor(cme, mq).sqrt()
                 * (17.0 * temp2 + 7.0 * temp3))
                 / (72.0
-                    * COS_THETA_WEAK.powi(2)
+                    * self.sm.cos_theta_weak().powi(2)
                     * cme.powi(2)
-                    * (-4.0 * temp1 + temp2).sqrt()
-                    * ((-temp2 + temp4).powi(2) + temp4 * self.widthv.powi(2)))
+                    * threshold_factor(cme, self.mx).sqrt()
+                    * self.propagator_denominator(temp2))
         } else {
             0.0
         }
     }
-    /// Compute the annihilation cross-section for dark matter to down-type quarks.
-    pub fn sigma_xx_to_qdqd(&self, cme: f64, mf: f64) -> f64 {
-        if cme > 2.0 * self.mx && cme > 2.0 * mf {
+    /// Compute the annihilation cross-section for dark matter to down-type
+    /// quarks, see `sigma_xx_to_ququ`.
+    pub fn sigma_xx_to_qdqd(&self, cme: f64, quark: Quark) -> f64 {
+        if cme > 2.0 * self.mx && cme > 2.0 * self.sm.quark_mass(quark) {
             let temp1: f64 = cme.powi(2);
             let temp2: f64 = self.mx.powi(2);
-            let temp3: f64 = mf.powi(2);
-            let temp4: f64 = self.mv.powi(2);
-            (ALPHA_EM
+            let mq = self.sm.quark_mass_at(quark, cme);
+            let temp3: f64 = mq.powi(2);
+            (self.sm.alpha_em_at(cme)
+                * self.sm.qcd_correction(cme)
                 * self.eps.powi(2)
                 * self.gvxx.powi(2)
                 * (temp1 + 2.0 * temp2)
                 * (5.0 * temp1 - 17.0 * temp3)
-                * (temp1 - 4.0 * temp3).sqrt())
+                * threshold_factor(cme, mq).sqrt())
                 / (72.0
-                    * COS_THETA_WEAK.powi(2)
+                    * self.sm.cos_theta_weak().powi(2)
                     * temp1
-                    * (temp1 - 4.0 * temp2).sqrt()
-                    * ((-temp1 + temp4).powi(2) + temp4 * self.widthv.powi(2)))
+                    * threshold_factor(cme, self.mx).sqrt()
+                    * self.propagator_denominator(temp1))
         } else {
             0.0
         }
@@ -52,126 +73,265 @@ impl KineticMixing {
             let temp1: f64 = self.mx.powi(2);
             let temp2: f64 = cme.powi(2);
             let temp3: f64 = mf.powi(2);
-            let temp4: f64 = self.mv.powi(2);
-            (ALPHA_EM
+            (self.sm.alpha_em_at(cme)
                 * self.eps.powi(2)
                 * self.gvxx.powi(2)
                 * (2.0 * temp1 + temp2)
-                * (temp2 - 4.0 * temp3).sqrt()
+                * threshold_factor(cme, mf).sqrt()
                 * (5.0 * temp2 + 7.0 * temp3))
                 / (24.0
-                    * COS_THETA_WEAK.powi(2)
+                    * self.sm.cos_theta_weak().powi(2)
                     * cme.powi(2)
-                    * (-4.0 * temp1 + temp2).sqrt()
-                    * ((-temp2 + temp4).powi(2) + temp4 * self.widthv.powi(2)))
+                    * threshold_factor(cme, self.mx).sqrt()
+                    * self.propagator_denominator(temp2))
         } else {
             0.0
         }
     }
     /// Compute the annihilation cross-section for dark matter to a vector mediators.
     pub fn sigma_xx_to_vv(&self, cme: f64) -> f64 {
-        if cme > 2.0 * self.mx && cme > 2.0 * self.mv {
-            let temp1: f64 = self.mx.powi(2);
-            let temp2: f64 = -4.0 * temp1;
-            let temp3: f64 = cme.powi(2);
-            let temp4: f64 = temp2 + temp3;
-            let temp5: f64 = self.mv.powi(4);
-            let temp6: f64 = self.mv.powi(2);
-            let temp7: f64 = -4.0 * temp6;
-            let temp8: f64 = temp3 + temp7;
-            let temp9: f64 = 2.0 * temp6;
-            let temp10: f64 = -temp3;
-            let temp11: f64 = temp10 + temp9;
-            let temp12: f64 = -2.0 * temp6;
-            let temp13: f64 = temp4.sqrt();
-            let temp14: f64 = temp8.sqrt();
-            let temp15: f64 = temp13 * temp14;
-            let temp16: f64 = 2.0 * temp1;
-            let temp17: f64 = temp12 + temp15 + temp3;
-            (self.gvxx.powi(4)
-                * ((-48.0
-                    * temp13
-                    * temp14
-                    * (4.0 * self.mx.powi(4) + temp1 * temp3 + 2.0 * temp5))
-                    / (temp5 + temp1 * temp8)
-                    + (48.0
-                        * (temp1 * (temp12 + temp2 + temp3) * std::f64::consts::LN_2
-                            + temp3 * temp6 * 2.0 * std::f64::consts::LN_2
-                            + (2.0 * temp1 * (temp16 + temp6) - temp3 * (temp1 + temp9))
-                                * (-(temp17.powi(2)
-                                    / (-(cme.powi(4)) + temp13 * temp14 * temp3 - 2.0 * temp5
-                                        + (-2.0 * temp13 * temp14 + 4.0 * temp3) * temp6
-                                        + 2.0 * temp1 * temp8)))
-                                    .ln()
-                            + temp11
-                                * (temp12 + temp16 + temp3)
-                                * (-(temp17 / (temp10 + temp15 + temp9))).ln()))
-                        / temp11))
-                / (384.0 * std::f64::consts::PI * cme.powi(2) * temp4)
+        self.sigma_xx_to_v1v2(cme, self.mv, self.mv)
+    }
+    /// Compute the annihilation cross-section for dark matter to two vector
+    /// mediators with masses `m1` and `m2`, keeping all couplings fixed. This
+    /// includes the symmetry factor of identical mediators, so that it equals
+    /// `sigma_xx_to_vv` when `m1 = m2 = mv`.
+    pub fn sigma_xx_to_v1v2(&self, cme: f64, m1: f64, m2: f64) -> f64 {
+        0.5 * self.sigma_xx_to_v1v2_with(cme, m1, m2, self.gvxx, self.gvxx)
+    }
+    /// Compute the annihilation cross-section for dark matter to two distinct
+    /// vector bosons with masses `m1` and `m2` and vector couplings `g1` and
+    /// `g2` to chi, through t- and u-channel exchange of chi.
+    ///
+    /// With `k = s - m1^2 - m2^2`, `p = sqrt((s - 4 mx^2) lambda(s, m1^2, m2^2) / s)`,
+    /// `n = s^2 + 4 mx^2 s + (m1^2 + m2^2)^2 - 8 mx^4 - 4 mx^2 (m1^2 + m2^2)`
+    /// and `c = (2 mx^2 + m1^2) (2 mx^2 + m2^2)`, the cross section is
+    /// `g1^2 g2^2 / (4 pi s (s - 4 mx^2)) (n L / k - p - 4 p c / (k^2 - p^2))`
+    /// with `L = ln((k + p) / (k - p))`. Since `k^2 - p^2 > 0` in the physical
+    /// region, the logarithm is `ln(1 + 2 p (k + p) / (k^2 - p^2))`, which is
+    /// free of cancellations both at the thresholds, `p -> 0`, and for
+    /// `s >> mx^2, m1^2, m2^2`.
+    pub(super) fn sigma_xx_to_v1v2_with(
+        &self,
+        cme: f64,
+        m1: f64,
+        m2: f64,
+        g1: f64,
+        g2: f64,
+    ) -> f64 {
+        if cme > 2.0 * self.mx && cme > m1 + m2 {
+            let s = cme * cme;
+            let mx2 = self.mx * self.mx;
+            let (m12, m22) = (m1 * m1, m2 * m2);
+            let dx = threshold_factor(cme, self.mx);
+            let lam = (cme - m1 - m2) * (cme + m1 + m2) * (cme - m1 + m2) * (cme + m1 - m2);
+            let p = (dx * lam / s).sqrt();
+            let k = s - m12 - m22;
+            let d = (k - p) * (k + p);
+            let n = s * s + 4.0 * mx2 * s + (m12 + m22).powi(2)
+                - 8.0 * mx2 * mx2
+                - 4.0 * mx2 * (m12 + m22);
+            let c = (2.0 * mx2 + m12) * (2.0 * mx2 + m22);
+            // L / p, finite as p -> 0.
+            let log_over_p = ln_1p_over_x(2.0 * p * (k + p) / d) * 2.0 * (k + p) / d;
+            (g1 * g2).powi(2) / (4.0 * std::f64::consts::PI * s) * p / dx
+                * (n * log_over_p / k - 1.0 - 4.0 * c / d)
         } else {
             0.0
         }
     }
+    /// Compute the annihilation cross-section for dark matter to an on-shell
+    /// vector mediator and a Standard Model fermion pair from an off-shell
+    /// mediator, chi + chibar -> V + V* -> V + f + fbar.
+    ///
+    /// Above the `V V` threshold the mediator can be on-shell, which is
+    /// already counted by `sigma_xx_to_vv`. The on-shell part, the same
+    /// integral with `sigma(V V(m)) m Gamma_SM(m)` frozen at `m = mv`, is
+    /// therefore subtracted over the same range `0 < m < sqrt(s) - mv`,
+    /// leaving the off-shell remainder. Over the full range the on-shell part
+    /// would be `2 BR(V -> SM) sigma_xx_to_vv`, the narrow-width
+    /// approximation. Both the subtraction and `sigma_xx_to_vv` vanish at
+    /// `sqrt(s) = 2 mv`, so the total cross section is continuous across the
+    /// threshold. The remainder can be negative, while the sum with
+    /// `sigma_xx_to_vv` is not.
+    pub fn sigma_xx_to_vff(&self, cme: f64) -> f64 {
+        if cme <= 2.0 * self.mx || cme <= self.mv {
+            return 0.0;
+        }
+        // sigma = 2 / pi int dm^2 sigma(V V(m)) m Gamma_SM(m) / D(m^2), with
+        // the factor 2 because either mediator can be off-shell. Below the
+        // V V threshold sigma_xx_to_vv, and with it the on-shell part,
+        // vanishes.
+        let on_shell =
+            self.sigma_xx_to_vv(cme) * self.mv * (self.vm_decay_width() - self.width_v_to_xx());
+        let integrand = |m: f64| -> f64 {
+            let width = self.vm_decay_width_at(m) - self.width_v_to_xx_at(m);
+            2.0 * m * (self.sigma_xx_to_v1v2(cme, self.mv, m) * m * width - on_shell)
+                / self.propagator_denominator(m * m)
+        };
+        let mmax = cme - self.mv;
+        // Resolve the peak of V* when it lies within or close to the range.
+        let mut singular_points = vec![self.mv];
+        for k in [1.0, 10.0, 100.0].iter() {
+            singular_points.push(self.mv - k * self.widthv);
+            singular_points.push(self.mv + k * self.widthv);
+        }
+        singular_points.retain(|&m| m > 0.0 && m < mmax);
+        singular_points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        singular_points.dedup();
+        let sigma = if singular_points.is_empty() {
+            VFF_INTEGRATOR.integrate(integrand, 0.0, mmax).val
+        } else {
+            GaussKronrodIntegratorBuilder::default()
+                .epsabs(0.0)
+                .epsrel(VFF_EPSREL)
+                .singular_points(singular_points)
+                .limit(200)
+                .build()
+                .integrate(integrand, 0.0, mmax)
+                .val
+        };
+        2.0 / std::f64::consts::PI * sigma
+    }
     /// Compute the annihilation cross-section for dark matter to neutrinos.
     pub fn sigma_xx_to_nunu(&self, cme: f64) -> f64 {
         if cme > 2.0 * self.mx {
             let temp1: f64 = cme.powi(2);
             let temp2: f64 = self.mx.powi(2);
-            let temp3: f64 = self.mv.powi(2);
-            (ALPHA_EM * self.eps.powi(2) * self.gvxx.powi(2) * temp1.sqrt() * (temp1 + 2.0 * temp2))
+            (self.sm.alpha_em_at(cme)
+                * self.eps.powi(2)
+                * self.gvxx.powi(2)
+                * temp1.sqrt()
+                * (temp1 + 2.0 * temp2))
                 / (24.0
-                    * COS_THETA_WEAK.powi(2)
-                    * (temp1 - 4.0 * temp2).sqrt()
-                    * ((-temp1 + temp3).powi(2) + temp3 * self.widthv.powi(2)))
+                    * self.sm.cos_theta_weak().powi(2)
+                    * threshold_factor(cme, self.mx).sqrt()
+                    * self.propagator_denominator(temp1))
         } else {
             0.0
         }
     }
     /// Compute the annihilation cross-section for dark matter to a higgs and z-boson.
     pub fn sigma_xx_to_hz(&self, cme: f64) -> f64 {
-        if cme > 2.0 * self.mx && cme > HIGGS_MASS + Z_BOSON_MASS {
+        if cme > 2.0 * self.mx && cme > self.sm.higgs_mass + self.sm.z_boson_mass {
             let temp1: f64 = -cme;
-            let temp2: f64 = -W_BOSON_MASS;
+            let temp2: f64 = -self.sm.w_boson_mass;
             let temp3: f64 = self.mx.powi(2);
             let temp4: f64 = cme.powi(2);
-            let temp5: f64 = W_BOSON_MASS.powi(2);
-            let temp6: f64 = self.mv.powi(2);
-            (ALPHA_EM.powi(2)
+            let temp5: f64 = self.sm.w_boson_mass.powi(2);
+            (self.sm.alpha_em_at(cme).powi(2)
                 * self.eps.powi(2)
                 * self.gvxx.powi(2)
                 * std::f64::consts::PI
-                * (((HIGGS_MASS + W_BOSON_MASS + cme)
-                    * (HIGGS_MASS + W_BOSON_MASS + temp1)
-                    * (HIGGS_MASS + cme + temp2)
-                    * (HIGGS_MASS + temp1 + temp2))
-                    / (-4.0 * temp3 + temp4))
-                    .sqrt()
+                * (((self.sm.higgs_mass + self.sm.w_boson_mass + cme)
+                    * (self.sm.higgs_mass + self.sm.w_boson_mass + temp1)
+                    * (self.sm.higgs_mass + cme + temp2)
+                    * (self.sm.higgs_mass + temp1 + temp2))
+                    / threshold_factor(cme, self.mx))
+                .sqrt()
                 * (2.0 * temp3 + temp4)
-                * (HIGGS_MASS.powi(4) + W_BOSON_MASS.powi(4) + cme.powi(4) + 10.0 * temp4 * temp5
-                    - 2.0 * HIGGS_MASS.powi(2) * (temp4 + temp5))
-                * HIGGS_VEV.powi(2))
+                * (self.sm.higgs_mass.powi(4)
+                    + self.sm.w_boson_mass.powi(4)
+                    + cme.powi(4)
+                    + 10.0 * temp4 * temp5
+                    - 2.0 * self.sm.higgs_mass.powi(2) * (temp4 + temp5))
+                * self.sm.higgs_vev.powi(2))
                 / (48.0
-                    * COS_THETA_WEAK.powi(4)
-                    * W_BOSON_MASS.powi(2)
+                    * self.sm.cos_theta_weak().powi(4)
+                    * self.sm.w_boson_mass.powi(2)
                     * cme.powi(5)
-                    * SIN_THETA_WEAK_SQRD
-                    * ((-temp4 + temp6).powi(2) + temp6 * self.widthv.powi(2)))
+                    * self.sm.sin_theta_weak_sqrd
+                    * self.propagator_denominator(temp4))
         } else {
             0.0
         }
     }
+    /// Compute the annihilation cross-section for dark matter to W+ + W-
+    /// through the Z-boson component of an s-channel `V`.
+    pub fn sigma_xx_to_ww(&self, cme: f64) -> f64 {
+        if cme > 2.0 * self.mx && cme > 2.0 * self.sm.w_boson_mass {
+            // sigma = gvxx^2 (s + 2 mx^2) Gamma(V* -> W W) / (sqrt(s - 4 mx^2) D(s))
+            let s = cme * cme;
+            let temp1: f64 = self.mx.powi(2);
+            self.gvxx.powi(2) * (s + 2.0 * temp1) * self.width_v_to_ww_at(cme)
+                / (threshold_factor(cme, self.mx).sqrt() * self.propagator_denominator(s))
+        } else {
+            0.0
+        }
+    }
+    /// Compute the annihilation cross-section for dark matter to a vector
+    /// mediator and a Z-boson, through t- and u-channel exchange of chi with
+    /// one vertex from the Z-boson component of `V`.
+    ///
+    /// To leading order in the mixing angle `theta_vz`, chi couples to `V`
+    /// with `gvxx` and to the Z-boson with `gvxx theta_vz`. The final-state
+    /// particles are distinct, so there is no symmetry factor.
+    ///
+    /// The corresponding `V + gamma` final state vanishes: chi does not couple
+    /// to the massless photon after the kinetic mixing is removed.
+    pub fn sigma_xx_to_vz(&self, cme: f64) -> f64 {
+        let gxxz = self.gvxx * self.vz_mixing_angle();
+        self.sigma_xx_to_v1v2_with(cme, self.mv, self.sm.z_boson_mass, self.gvxx, gxxz)
+    }
     pub fn annihilation_cross_section(&self, cme: f64) -> f64 {
-        self.sigma_xx_to_ququ(cme, UP_QUARK_MASS)
-            + self.sigma_xx_to_ququ(cme, CHARM_QUARK_MASS)
-            + self.sigma_xx_to_ququ(cme, TOP_QUARK_MASS)
-            + self.sigma_xx_to_qdqd(cme, DOWN_QUARK_MASS)
-            + self.sigma_xx_to_qdqd(cme, STRANGE_QUARK_MASS)
-            + self.sigma_xx_to_qdqd(cme, BOTTOM_QUARK_MASS)
-            + self.sigma_xx_to_ll(cme, ELECTRON_MASS)
-            + self.sigma_xx_to_ll(cme, MUON_MASS)
-            + self.sigma_xx_to_ll(cme, TAU_MASS)
-            + 3.0 * self.sigma_xx_to_hz(cme)
+        self.sigma_xx_to_ququ(cme, Quark::Up)
+            + self.sigma_xx_to_ququ(cme, Quark::Charm)
+            + self.sigma_xx_to_ququ(cme, Quark::Top)
+            + self.sigma_xx_to_qdqd(cme, Quark::Down)
+            + self.sigma_xx_to_qdqd(cme, Quark::Strange)
+            + self.sigma_xx_to_qdqd(cme, Quark::Bottom)
+            + self.sigma_xx_to_ll(cme, self.sm.electron_mass)
+            + self.sigma_xx_to_ll(cme, self.sm.muon_mass)
+            + self.sigma_xx_to_ll(cme, self.sm.tau_mass)
+            + 3.0 * self.sigma_xx_to_nunu(cme)
             + self.sigma_xx_to_hz(cme)
             + self.sigma_xx_to_vv(cme)
+            + self.sigma_xx_to_vff(cme)
+            + self.sigma_xx_to_ww(cme)
+            + self.sigma_xx_to_vz(cme)
+    }
+    /// Compute the annihilation cross-section for dark matter into the final
+    /// state `fs`. Elastic scattering, `XX`, does not contribute.
+    pub fn partial_annihilation_cross_section(
+        &self,
+        cme: f64,
+        fs: KineticMixingFinalStates,
+    ) -> f64 {
+        match fs {
+            KineticMixingFinalStates::All => self.annihilation_cross_section(cme),
+            KineticMixingFinalStates::XX => 0.0,
+            KineticMixingFinalStates::UU => self.sigma_xx_to_ququ(cme, Quark::Up),
+            KineticMixingFinalStates::CC => self.sigma_xx_to_ququ(cme, Quark::Charm),
+            KineticMixingFinalStates::TT => self.sigma_xx_to_ququ(cme, Quark::Top),
+            KineticMixingFinalStates::DD => self.sigma_xx_to_qdqd(cme, Quark::Down),
+            KineticMixingFinalStates::SS => self.sigma_xx_to_qdqd(cme, Quark::Strange),
+            KineticMixingFinalStates::BB => self.sigma_xx_to_qdqd(cme, Quark::Bottom),
+            KineticMixingFinalStates::EE => self.sigma_xx_to_ll(cme, self.sm.electron_mass),
+            KineticMixingFinalStates::MuMu => self.sigma_xx_to_ll(cme, self.sm.muon_mass),
+            KineticMixingFinalStates::TauTau => self.sigma_xx_to_ll(cme, self.sm.tau_mass),
+            KineticMixingFinalStates::NueNue => self.sigma_xx_to_nunu(cme),
+            KineticMixingFinalStates::NumuNumu => self.sigma_xx_to_nunu(cme),
+            KineticMixingFinalStates::NutauNutau => self.sigma_xx_to_nunu(cme),
+            KineticMixingFinalStates::HiggsZ => self.sigma_xx_to_hz(cme),
+            KineticMixingFinalStates::VV => self.sigma_xx_to_vv(cme),
+            KineticMixingFinalStates::VFF => self.sigma_xx_to_vff(cme),
+            KineticMixingFinalStates::WW => self.sigma_xx_to_ww(cme),
+            KineticMixingFinalStates::VZ => self.sigma_xx_to_vz(cme),
+        }
+    }
+}
+
+/// Compute `s - 4 m^2` as `(cme - 2 m) (cme + 2 m)`, which keeps its relative
+/// precision as `cme -> 2 m`.
+fn threshold_factor(cme: f64, m: f64) -> f64 {
+    (cme - 2.0 * m) * (cme + 2.0 * m)
+}
+
+/// Compute `ln(1 + z) / z`, using its series for small `z`.
+fn ln_1p_over_x(z: f64) -> f64 {
+    if z.abs() < 1e-5 {
+        1.0 - z * (0.5 - z / 3.0)
+    } else {
+        z.ln_1p() / z
     }
 }
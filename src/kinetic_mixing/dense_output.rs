@@ -0,0 +1,158 @@
+use super::boltzmann::X_END;
+use super::KineticMixing;
+use crate::error::{check_parameter, ModelError};
+use cyphus_diffeq::prelude::*;
+use ndarray::prelude::*;
+
+/// Solution of the Boltzmann equation that can be evaluated at any `x` in
+/// the integration range.
+///
+/// `log(Y)` is interpolated in `log(x)` with piecewise cubic Hermite
+/// polynomials. The slopes at the steps of the solver are taken from the
+/// Boltzmann equation itself and limited following Fritsch and Carlson, so
+/// the interpolant is monotone wherever the solution is.
+#[derive(Clone)]
+pub struct BoltzmannSolution {
+    km: KineticMixing,
+    logx: Vec<f64>,
+    logy: Vec<f64>,
+    slopes: Vec<f64>,
+}
+
+/// Abundance of chi and its derivative on a grid of `x`.
+#[derive(Clone, Debug)]
+pub struct SampledSolution {
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub dy_dx: Vec<f64>,
+    pub yeq: Vec<f64>,
+}
+
+impl BoltzmannSolution {
+    /// Build the interpolant from a solution of the Boltzmann equation for
+    /// `log(Y)` as a function of `log(x)`.
+    pub fn new(km: &KineticMixing, sol: &OdeSolution) -> BoltzmannSolution {
+        let logx: Vec<f64> = sol.ts.clone();
+        let logy: Vec<f64> = sol.us.iter().map(|u| u[0]).collect();
+        let mut slopes: Vec<f64> = logx
+            .iter()
+            .zip(logy.iter())
+            .map(|(&t, &w)| {
+                let mut dw = Array1::<f64>::zeros(1);
+                km.dudt(dw.view_mut(), array![w].view(), t);
+                dw[0]
+            })
+            .collect();
+        limit_slopes(&logx, &logy, &mut slopes);
+        BoltzmannSolution {
+            km: km.clone(),
+            logx,
+            logy,
+            slopes,
+        }
+    }
+    /// Range of `x` covered by the solution.
+    pub fn x_range(&self) -> (f64, f64) {
+        (self.logx[0].exp(), self.logx.last().unwrap().exp())
+    }
+    /// Evaluate `log(Y)` and `dlog(Y)/dlog(x)` at `x`, failing if `x` is
+    /// outside of `x_range`.
+    fn eval(&self, x: f64) -> Result<(f64, f64), ModelError> {
+        let (x0, x1) = self.x_range();
+        check_parameter("x", x, "x within the range of the solution", |x| {
+            x >= x0 * (1.0 - 1e-12) && x <= x1 * (1.0 + 1e-12)
+        })?;
+        let n = self.logx.len();
+        if n == 1 {
+            return Ok((self.logy[0], self.slopes[0]));
+        }
+        let t = x.ln();
+        let i = self.logx.partition_point(|&ti| ti <= t).clamp(1, n - 1) - 1;
+        let h = self.logx[i + 1] - self.logx[i];
+        let s = ((t - self.logx[i]) / h).clamp(0.0, 1.0);
+        let (y0, y1) = (self.logy[i], self.logy[i + 1]);
+        let (m0, m1) = (self.slopes[i] * h, self.slopes[i + 1] * h);
+
+        let h00 = (1.0 + 2.0 * s) * (1.0 - s) * (1.0 - s);
+        let h10 = s * (1.0 - s) * (1.0 - s);
+        let h01 = s * s * (3.0 - 2.0 * s);
+        let h11 = s * s * (s - 1.0);
+        let w = h00 * y0 + h10 * m0 + h01 * y1 + h11 * m1;
+
+        let d00 = 6.0 * s * (s - 1.0);
+        let d10 = (1.0 - s) * (1.0 - 3.0 * s);
+        let d11 = s * (3.0 * s - 2.0);
+        let dw = (d00 * (y0 - y1) + d10 * m0 + d11 * m1) / h;
+        Ok((w, dw))
+    }
+    /// Comoving abundance `Y` of chi at `x`, which must lie within `x_range`.
+    pub fn y(&self, x: f64) -> Result<f64, ModelError> {
+        Ok(self.eval(x)?.0.exp())
+    }
+    /// Derivative `dY/dx` at `x`, which must lie within `x_range`.
+    pub fn dy_dx(&self, x: f64) -> Result<f64, ModelError> {
+        let (w, dw) = self.eval(x)?;
+        Ok(w.exp() * dw / x)
+    }
+    /// Equilibrium abundance of chi at `x`.
+    pub fn yeq(&self, x: f64) -> f64 {
+        let temp = self.km.mx / x;
+        self.km.neq(temp) / self.km.cosmology.entropy_density(temp)
+    }
+    /// Evaluate the solution on the grid `xs`, failing if any point lies
+    /// outside of `x_range`.
+    pub fn resample(&self, xs: &[f64]) -> Result<SampledSolution, ModelError> {
+        let mut y = Vec::with_capacity(xs.len());
+        let mut dy_dx = Vec::with_capacity(xs.len());
+        for &x in xs.iter() {
+            let (w, dw) = self.eval(x)?;
+            y.push(w.exp());
+            dy_dx.push(w.exp() * dw / x);
+        }
+        Ok(SampledSolution {
+            x: xs.to_vec(),
+            y,
+            dy_dx,
+            yeq: xs.iter().map(|&x| self.yeq(x)).collect(),
+        })
+    }
+}
+
+/// Limit the slopes `m` of a cubic Hermite interpolant through `(t, y)` so
+/// that it is monotone on every interval where the data are monotone.
+fn limit_slopes(t: &[f64], y: &[f64], m: &mut [f64]) {
+    for i in 0..t.len().saturating_sub(1) {
+        let d = (y[i + 1] - y[i]) / (t[i + 1] - t[i]);
+        if d == 0.0 {
+            m[i] = 0.0;
+            m[i + 1] = 0.0;
+            continue;
+        }
+        if m[i] * d < 0.0 {
+            m[i] = 0.0;
+        }
+        if m[i + 1] * d < 0.0 {
+            m[i + 1] = 0.0;
+        }
+        let (a, b) = (m[i] / d, m[i + 1] / d);
+        let r = a * a + b * b;
+        if r > 9.0 {
+            let tau = 3.0 / r.sqrt();
+            m[i] = tau * a * d;
+            m[i + 1] = tau * b * d;
+        }
+    }
+}
+
+impl KineticMixing {
+    /// Solve the Boltzmann equation and return a solution that can be
+    /// evaluated at any `x` between 1 and 1000.
+    ///
+    /// Fails like `relic_density` if the integration stops early or the
+    /// abundance becomes non-finite.
+    pub fn dense_boltzmann_solution(&self) -> Result<BoltzmannSolution, ModelError> {
+        let sol = self.solve_boltzmann();
+        self.check_boltzmann_solution(&sol, X_END)?;
+        Ok(BoltzmannSolution::new(self, &sol))
+    }
+}
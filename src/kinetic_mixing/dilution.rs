@@ -0,0 +1,282 @@
+use super::boltzmann::X_END;
+use super::reheating::{
+    dlogneq_dlogt, log_derivative, mean_energy, temperature_from_entropy_density,
+};
+use super::KineticMixing;
+use crate::cosmology::hubble_rate_from_energy_density;
+use crate::error::{check_parameter, check_solution, ModelError};
+use crate::statistics::{equilibrium_number_density, Statistics};
+use cyphus_diffeq::prelude::*;
+use cyphus_specfun::bessel::CylBesselK;
+use haliax_constants::prelude::*;
+use ndarray::prelude::*;
+
+/// Value of `x = mx / T` at which the dilution integration starts.
+const DILUTION_X_START: f64 = 20.0;
+
+/// Relic density corrected for the entropy released by a long-lived mediator.
+#[derive(Clone, Copy, Debug)]
+pub struct DilutedRelicDensity {
+    /// Relic density assuming no entropy injection.
+    pub omega_undiluted: f64,
+    /// Relic density after dilution, `omega_undiluted / dilution`.
+    pub omega: f64,
+    /// Ratio of the comoving entropy after and before the mediator decays.
+    pub dilution: f64,
+    /// Lifetime of the mediator in GeV^-1.
+    pub lifetime: f64,
+}
+
+/// Evolution of the mediator abundance and the entropy of the bath after chi
+/// freeze-out as a function of the logarithm of the scale factor. The
+/// unknowns are `[log(n_v a^3 mv / rho_0), log(s a^3 / s_0)]` with `rho_0`
+/// and `s_0` the radiation energy and entropy densities at the start of the
+/// integration.
+#[derive(Clone)]
+struct DilutionSystem {
+    km: KineticMixing,
+    rho_0: f64,
+    s_0: f64,
+    /// Comoving number density of chi, `n_chi a^3`.
+    nchi_0: f64,
+}
+
+impl DilutionSystem {
+    /// Compute `(H, T, n_v, s)` for the state `w` at `loga`.
+    fn state(&self, w: ArrayView1<f64>, loga: f64) -> Result<(f64, f64, f64, f64), ModelError> {
+        let (mx, mv) = (self.km.mx, self.km.mv);
+        let cosmology = self.km.cosmology.as_ref();
+        let nv = self.rho_0 / mv * (w[0] - 3.0 * loga).exp();
+        let s = self.s_0 * (w[1] - 3.0 * loga).exp();
+        let temp = temperature_from_entropy_density(cosmology, s)?;
+        let rho = cosmology.bath_energy_density(temp)
+            + mean_energy(mv, temp) * nv
+            + mx * self.nchi_0 * (-3.0 * loga).exp();
+        Ok((hubble_rate_from_energy_density(rho), temp, nv, s))
+    }
+    fn dudt(&self, mut dw: ArrayViewMut1<f64>, w: ArrayView1<f64>, loga: f64) {
+        let mv = self.km.mv;
+        let (hubble, temp, nv, s) = match self.state(w, loga) {
+            Ok(state) => state,
+            Err(_) => {
+                dw.fill(f64::NAN);
+                return;
+            }
+        };
+        let z = mv / temp;
+        let nveq = equilibrium_number_density(temp, mv, 3.0, Statistics::MaxwellBoltzmann);
+        let rate = self.km.widthv * z.cyl_bessel_k1_scaled() / z.cyl_bessel_kn_scaled(2);
+
+        // Long after V has decayed n_v underflows, so n_v,eq / n_v is formed
+        // in the exponent.
+        let lognv = (self.rho_0 / mv).ln() + w[0] - 3.0 * loga;
+        dw[0] = -rate * (1.0 - (nveq.ln() - lognv).exp()) / hubble;
+        // Time dilation cancels against the boosted energy: decays release
+        // Gamma mv n_v, raising the entropy as T ds = dq.
+        dw[1] = self.km.widthv * mv * (nv - nveq) / (hubble * temp * s);
+    }
+    /// Compute the Jacobian of `dudt`. Only `w[1]` changes the temperature,
+    /// through `d log(T) / d w[1] = 1 / (d log(s) / d log(T))`. The mean
+    /// energy of `V` is held fixed in the Hubble rate.
+    fn dfdu(&self, mut dw: ArrayViewMut2<f64>, w: ArrayView1<f64>, loga: f64) {
+        let mv = self.km.mv;
+        let cosmology = self.km.cosmology.as_ref();
+        let (hubble, temp, nv, s) = match self.state(w, loga) {
+            Ok(state) => state,
+            Err(_) => {
+                dw.fill(f64::NAN);
+                return;
+            }
+        };
+        let z = mv / temp;
+        let (k1, k2) = (z.cyl_bessel_k1_scaled(), z.cyl_bessel_kn_scaled(2));
+        let nveq = equilibrium_number_density(temp, mv, 3.0, Statistics::MaxwellBoltzmann);
+        let rate = self.km.widthv * k1 / k2;
+        let lognv = (self.rho_0 / mv).ln() + w[0] - 3.0 * loga;
+        let ratio = (nveq.ln() - lognv).exp();
+
+        let dlogt = 1.0 / log_derivative(|t| cosmology.entropy_density(t), temp);
+        let rhor = cosmology.bath_energy_density(temp);
+        let rhov = mean_energy(mv, temp) * nv;
+        let rho = rhor + rhov + self.km.mx * self.nchi_0 * (-3.0 * loga).exp();
+        let drhor = rhor * log_derivative(|t| cosmology.bath_energy_density(t), temp);
+        // d log(H) / d w[j]
+        let dlogh = [0.5 * rhov / rho, 0.5 * drhor * dlogt / rho];
+        // d log(K1(z) / K2(z)) / d log(T), using K0 = K2 - 2 K1 / z.
+        let dlograte = z * k2 / k1 - z * k1 / k2 - 3.0;
+        let dlognveq = dlogneq_dlogt(mv, temp);
+
+        let f0 = -rate * (1.0 - ratio) / hubble;
+        let f1 = self.km.widthv * mv * (nv - nveq) / (hubble * temp * s);
+        dw[[0, 0]] = -rate * ratio / hubble - f0 * dlogh[0];
+        dw[[0, 1]] =
+            f0 * dlograte * dlogt + rate * ratio * dlognveq * dlogt / hubble - f0 * dlogh[1];
+        dw[[1, 0]] = self.km.widthv * mv * nv / (hubble * temp * s) - f1 * dlogh[0];
+        dw[[1, 1]] = -self.km.widthv * mv * nveq * dlognveq * dlogt / (hubble * temp * s)
+            - f1 * (dlogh[1] + dlogt + 1.0);
+    }
+}
+
+impl KineticMixing {
+    /// Compute the lifetime of the vector mediator in GeV^-1.
+    pub fn vm_lifetime(&self) -> f64 {
+        1.0 / self.widthv
+    }
+    /// Check that the mediator decays, with a finite lifetime, and cannot
+    /// decay into chi + chibar.
+    fn check_dilution_parameters(&self) -> Result<(), ModelError> {
+        check_parameter("mv", self.mv, "mv < 2 mx for dilution", |mv| {
+            mv < 2.0 * self.mx
+        })?;
+        check_parameter(
+            "widthv",
+            self.widthv,
+            "a positive width with a finite lifetime, e.g. eps > 0",
+            |w| w > 0.0 && (1.0 / w).is_finite(),
+        )
+    }
+    /// Solve for the mediator abundance and the entropy of the bath after chi
+    /// freeze-out, given the frozen-out chi abundance `ychi` and the mediator
+    /// abundance `yv = n_v / s` at `T_0 = mx / 20`, e.g. from
+    /// `vm_equilibrium_abundance`.
+    ///
+    /// The mediator is afterwards only depleted by decays and replenished by
+    /// inverse decays. The bath has the degrees of freedom of `cosmology`, but
+    /// only the bath, the mediator and chi contribute to the expansion rate.
+    ///
+    /// The solution contains `[log(n_v a^3 mv / rho_0), log(s a^3 / s_0)]` as a
+    /// function of `log(a / a_0)`, so the last entry ends at the log of the
+    /// dilution.
+    ///
+    /// Fails if `mv >= 2 mx`, the mediator does not decay, `yv` is not
+    /// positive, `ychi` is negative or the initial temperature of the bath
+    /// cannot be found.
+    pub fn solve_dilution(&self, ychi: f64, yv: f64) -> Result<OdeSolution, ModelError> {
+        self.check_dilution_parameters()?;
+        check_parameter("yv", yv, "yv > 0", |y| y > 0.0)?;
+        check_parameter("ychi", ychi, "ychi >= 0", |y| y >= 0.0)?;
+        let sys = self.dilution_system(ychi);
+        let nv = yv * self.cosmology.entropy_density(self.mx / DILUTION_X_START);
+        let uinit = array![(nv * self.mv / sys.rho_0).ln(), 0.0];
+
+        let tspan = (0.0, self.dilution_loga_end(&sys, uinit.view())?);
+
+        let dudt =
+            |mut dw: ArrayViewMut1<f64>, w: ArrayView1<f64>, loga: f64, p: &DilutionSystem| {
+                p.dudt(dw.view_mut(), w.view(), loga)
+            };
+        let dfdu =
+            |mut dw: ArrayViewMut2<f64>, w: ArrayView1<f64>, loga: f64, p: &DilutionSystem| {
+                p.dfdu(dw.view_mut(), w.view(), loga)
+            };
+
+        let mut integrator = OdeIntegratorBuilder::default(&dudt, uinit, tspan, Radau5, sys)
+            .dfdu(&dfdu)
+            .reltol(self.ode_reltol)
+            .abstol(self.ode_abstol)
+            .build();
+
+        integrator.integrate();
+        Ok(integrator.sol)
+    }
+    /// Compute `log(a / a_0)` at which the dilution integration starting from
+    /// `uinit` stops.
+    fn dilution_loga_end(
+        &self,
+        sys: &DilutionSystem,
+        uinit: ArrayView1<f64>,
+    ) -> Result<f64, ModelError> {
+        // Integrate until H has fallen well below the width. H falls at least
+        // as fast as a^(-3/2), during both radiation and mediator domination.
+        let hubble_0 = sys.state(uinit, 0.0)?.0;
+        Ok((2.0 / 3.0 * (hubble_0 / self.widthv).ln()).max(0.0) + 5.0)
+    }
+    /// Compute the equilibrium abundance `Y_v = n_v / s` of the mediator at
+    /// `x = mx / T`. A mediator that stops changing its number at `x`, e.g.
+    /// once `chi + chibar <-> V + V` and the scattering with the bath fall
+    /// below the Hubble rate, keeps this abundance until it decays.
+    pub fn vm_equilibrium_abundance(&self, x: f64) -> f64 {
+        let temp = self.mx / x;
+        equilibrium_number_density(temp, self.mv, 3.0, Statistics::MaxwellBoltzmann)
+            / self.cosmology.entropy_density(temp)
+    }
+    fn dilution_system(&self, ychi: f64) -> DilutionSystem {
+        let temp = self.mx / DILUTION_X_START;
+        DilutionSystem {
+            km: self.clone(),
+            rho_0: self.cosmology.bath_energy_density(temp),
+            s_0: self.cosmology.entropy_density(temp),
+            nchi_0: ychi * self.cosmology.entropy_density(temp),
+        }
+    }
+    /// Compute the relic density including the dilution from the entropy
+    /// released when a long-lived mediator decays after chi freeze-out.
+    ///
+    /// The decays into chi + chibar and the number-changing processes of the
+    /// dark sector are neglected after freeze-out, so this is only meaningful
+    /// for `mv < 2 mx`.
+    ///
+    /// The mediator starts from the abundance `yv = n_v / s` at
+    /// `T_0 = mx / 20`, which depends on when its number-changing processes
+    /// decouple and is therefore an input, e.g.
+    /// `vm_equilibrium_abundance(x_dec)` for decoupling at `x_dec`. For small
+    /// `eps` the mediator can be long-lived while still carrying this
+    /// abundance. Fails if `mv >= 2 mx`, if the mediator does not decay, e.g.
+    /// for `eps == 0`, if `yv` is not positive or if either integration stops
+    /// early or its solution becomes non-finite.
+    pub fn relic_density_with_dilution(&self, yv: f64) -> Result<DilutedRelicDensity, ModelError> {
+        self.check_dilution_parameters()?;
+        let sol = self.solve_boltzmann();
+        self.check_boltzmann_solution(&sol, X_END)?;
+        let ychi = sol.us[sol.us.len() - 1][0].exp();
+        let omega_undiluted = ychi * self.mx * S_TODAY / RHO_CRIT;
+
+        let sys = self.dilution_system(ychi);
+        let sol = self.solve_dilution(ychi, yv)?;
+        let loga_end = self.dilution_loga_end(&sys, sol.us[0].view())?;
+        let x_of = |loga: f64, u: ArrayView1<f64>| {
+            sys.state(u, loga)
+                .map_or(f64::NAN, |state| self.mx / state.1)
+        };
+        // Only reported on failure: x grows as a at constant entropy and h_eff.
+        let x_end = DILUTION_X_START * loga_end.exp();
+        check_solution(&sol, loga_end, x_end, x_of)?;
+        let dilution = sol.us[sol.us.len() - 1][1].exp();
+
+        Ok(DilutedRelicDensity {
+            omega_undiluted,
+            omega: omega_undiluted / dilution,
+            dilution,
+            lifetime: self.vm_lifetime(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::reheating::finite_difference_jacobian;
+    use super::*;
+
+    #[test]
+    fn test_dilution_jacobian() {
+        let km = KineticMixing::new(1e3, 1e2, 1.0, 1e-10).unwrap();
+        let (ychi, yv) = (1e-12, km.vm_equilibrium_abundance(1.0));
+        let sys = km.dilution_system(ychi);
+        let sol = km.solve_dilution(ychi, yv).unwrap();
+        // The column of V is exact; that of the entropy holds the mean energy
+        // of V fixed, which only enters through the Hubble rate.
+        for i in [sol.ts.len() / 4, sol.ts.len() / 2].iter() {
+            let (w, loga) = (sol.us[*i].view(), sol.ts[*i]);
+            let mut analytic = Array2::<f64>::zeros((2, 2));
+            let mut numerical = Array2::<f64>::zeros((2, 2));
+            sys.dfdu(analytic.view_mut(), w, loga);
+            let f = |df: ArrayViewMut1<f64>, u: ArrayView1<f64>| sys.dudt(df, u, loga);
+            finite_difference_jacobian(f, numerical.view_mut(), w);
+            for r in 0..2 {
+                let scale = numerical.row(r).iter().fold(0.0f64, |m, d| m.max(d.abs()));
+                assert!((analytic[[r, 0]] - numerical[[r, 0]]).abs() <= 1e-5 * scale);
+                assert!((analytic[[r, 1]] - numerical[[r, 1]]).abs() <= 1e-2 * scale);
+            }
+        }
+    }
+}
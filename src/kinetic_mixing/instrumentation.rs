@@ -0,0 +1,156 @@
+use super::thermal_cross_section::ThermalAverage;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Work done while solving the Boltzmann equation.
+///
+/// The Radau5 integrator of `cyphus-diffeq` stores the solution at every
+/// accepted step, which gives the number of accepted steps. It does not
+/// report its rejected steps, and the Gauss-Kronrod integrator of
+/// `cyphus-integration` does not report its subintervals, so these are not
+/// included; the integrand evaluations of the thermal averages measure their
+/// cost instead.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SolverStatistics {
+    /// Evaluations of the right-hand side of the Boltzmann equation.
+    pub rhs_evaluations: usize,
+    /// Evaluations of the Jacobian of the Boltzmann equation.
+    pub jacobian_evaluations: usize,
+    /// Steps accepted by the ODE integrator.
+    pub accepted_steps: usize,
+    /// Calls to `thermal_cross_section`.
+    pub thermal_averages: usize,
+    /// Thermal averages whose error estimate exceeds the tolerance.
+    pub unconverged_averages: usize,
+    /// Evaluations of the integrand of the thermal averages.
+    pub integrand_evaluations: usize,
+    /// Largest relative error estimate of a thermal average.
+    pub max_relative_error: f64,
+    /// Time spent in thermal averages.
+    pub thermal_average_time: Duration,
+    /// Total time spent solving the Boltzmann equation.
+    pub total_time: Duration,
+}
+
+/// Counters updated by a model while it is used by the integrators.
+#[derive(Debug, Default)]
+pub(super) struct Counters {
+    rhs_evaluations: AtomicUsize,
+    jacobian_evaluations: AtomicUsize,
+    thermal_averages: AtomicUsize,
+    unconverged_averages: AtomicUsize,
+    integrand_evaluations: AtomicUsize,
+    /// Bits of the largest relative error, which is non-negative so that the
+    /// bits order like the values.
+    max_relative_error: AtomicU64,
+    /// Time spent in thermal averages in nanoseconds.
+    thermal_average_nanos: AtomicU64,
+}
+
+fn increment(c: &AtomicUsize) {
+    c.fetch_add(1, Ordering::Relaxed);
+}
+
+impl Counters {
+    pub(super) fn count_rhs(&self) {
+        increment(&self.rhs_evaluations);
+    }
+    pub(super) fn count_jacobian(&self) {
+        increment(&self.jacobian_evaluations);
+    }
+    pub(super) fn count_integrand(&self) {
+        increment(&self.integrand_evaluations);
+    }
+    /// Record a thermal average at `x` that took `elapsed` to compute.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(super) fn record_thermal_average(&self, x: f64, avg: &ThermalAverage, elapsed: Duration) {
+        increment(&self.thermal_averages);
+        self.thermal_average_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        let relerr = if avg.val != 0.0 {
+            (avg.err / avg.val).abs()
+        } else {
+            avg.err.abs()
+        };
+        // A NaN is recorded as infinite so that it is never overwritten.
+        let relerr = if relerr.is_nan() {
+            f64::INFINITY
+        } else {
+            relerr
+        };
+        self.max_relative_error
+            .fetch_max(relerr.to_bits(), Ordering::Relaxed);
+        if !avg.converged {
+            increment(&self.unconverged_averages);
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                x,
+                val = avg.val,
+                err = avg.err,
+                "thermal average did not converge"
+            );
+        }
+    }
+    /// Collect the counters into `SolverStatistics`.
+    pub(super) fn statistics(
+        &self,
+        accepted_steps: usize,
+        total_time: Duration,
+    ) -> SolverStatistics {
+        SolverStatistics {
+            rhs_evaluations: self.rhs_evaluations.load(Ordering::Relaxed),
+            jacobian_evaluations: self.jacobian_evaluations.load(Ordering::Relaxed),
+            accepted_steps,
+            thermal_averages: self.thermal_averages.load(Ordering::Relaxed),
+            unconverged_averages: self.unconverged_averages.load(Ordering::Relaxed),
+            integrand_evaluations: self.integrand_evaluations.load(Ordering::Relaxed),
+            max_relative_error: f64::from_bits(self.max_relative_error.load(Ordering::Relaxed)),
+            thermal_average_time: Duration::from_nanos(
+                self.thermal_average_nanos.load(Ordering::Relaxed),
+            ),
+            total_time,
+        }
+    }
+}
+
+/// Counters of a model. Cloning a model gives it fresh counters, so that
+/// copies of a template model used by different solves or threads do not
+/// aggregate each other's counts; use `shared` to hand out the same counters.
+#[derive(Debug, Default)]
+pub(super) struct CounterHandle {
+    counters: Arc<Counters>,
+    /// Whether clones keep updating `counters`.
+    shared: bool,
+}
+
+impl CounterHandle {
+    /// Create a handle updating `counters`. Clones of the handle, e.g. of the
+    /// model passed to an integrator as its parameters, update the same
+    /// counters, so that a solve is counted however often the integrator
+    /// copies its parameters.
+    pub(super) fn shared(counters: &Arc<Counters>) -> CounterHandle {
+        CounterHandle {
+            counters: counters.clone(),
+            shared: true,
+        }
+    }
+}
+
+impl Clone for CounterHandle {
+    fn clone(&self) -> CounterHandle {
+        if self.shared {
+            CounterHandle::shared(&self.counters)
+        } else {
+            CounterHandle::default()
+        }
+    }
+}
+
+impl Deref for CounterHandle {
+    type Target = Counters;
+    fn deref(&self) -> &Counters {
+        &self.counters
+    }
+}
@@ -0,0 +1,89 @@
+use super::KineticMixing;
+
+/// Treatment of the width in the s-channel `V` propagator shared by all
+/// annihilation channels.
+///
+/// There is no separate complex-mass scheme. It replaces `mv^2` by the
+/// complex pole `mp^2 - i mp wp` everywhere, but the cross sections only
+/// contain the squared modulus `|s - mp^2 + i mp wp|^2` of a single
+/// s-channel `V`, with real couplings. This is the `Fixed` denominator
+/// written in terms of the pole mass and width, which is what `PoleMass`
+/// evaluates after converting the on-shell `mv` and `widthv`. The complex
+/// couplings of the complex-mass scheme, e.g. a complex `V`-`Z` mixing, only
+/// matter at higher order in `widthv / mv` and are not included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WidthScheme {
+    /// Constant width `widthv` computed at `sqrt(s) = mv`, with denominator
+    /// `(s - mv^2)^2 + mv^2 widthv^2`.
+    Fixed,
+    /// Energy-dependent width, with denominator
+    /// `(s - mv^2)^2 + s Gamma(sqrt(s))^2` and all partial widths evaluated
+    /// at `sqrt(s)`. A width fixed through `KineticMixingBuilder::width` or
+    /// `set_width` sets `Gamma(mv)`, with the energy dependence still taken
+    /// from the partial widths.
+    Running,
+    /// Constant width in terms of the pole mass and width, converted from the
+    /// on-shell `mv` and `widthv` as `mp^2 = mv^2 / (1 + widthv^2 / mv^2)` and
+    /// `wp = widthv mp / mv`, with denominator `(s - mp^2)^2 + mp^2 wp^2`.
+    /// Only the propagator is changed; the couplings, widths and the `V`-`Z`
+    /// mixing keep using `mv`. This is the complex-mass scheme for the
+    /// squared propagator, see above.
+    PoleMass,
+}
+
+impl KineticMixing {
+    /// Compute the width of `V` at `sqrt(s) = q` used by `WidthScheme::Running`,
+    /// scaled such that it equals `widthv` at `q = mv`.
+    fn running_width(&self, q: f64) -> f64 {
+        match self.running_width_scale {
+            Some(scale) => scale * self.vm_decay_width_at(q),
+            None => self.widthv,
+        }
+    }
+    /// Compute the squared modulus of the inverse `V` propagator in the
+    /// selected width scheme; see `WidthScheme` for the form in each scheme.
+    pub fn propagator_denominator(&self, s: f64) -> f64 {
+        let mv2 = self.mv * self.mv;
+        match self.width_scheme {
+            WidthScheme::Fixed => (s - mv2).powi(2) + mv2 * self.widthv.powi(2),
+            WidthScheme::Running => (s - mv2).powi(2) + s * self.running_width(s.sqrt()).powi(2),
+            WidthScheme::PoleMass => {
+                // mv_pole^2 = mv^2 / (1 + widthv^2 / mv^2), widthv_pole = widthv mv_pole / mv
+                let r2 = 1.0 / (1.0 + (self.widthv / self.mv).powi(2));
+                let mp2 = mv2 * r2;
+                (s - mp2).powi(2) + mp2 * self.widthv.powi(2) * r2
+            }
+        }
+    }
+    /// Compute the position `s0` of the minimum of `propagator_denominator`
+    /// and its value there, such that near the resonance the denominator is
+    /// `(s - s0)^2 + propagator_denominator(s0)`. This is exact for `Fixed`
+    /// and `PoleMass`, and holds to leading order in `widthv / mv` for
+    /// `Running`, where the width varies across the peak.
+    pub(super) fn propagator_peak(&self) -> (f64, f64) {
+        let mv2 = self.mv * self.mv;
+        let s0 = match self.width_scheme {
+            WidthScheme::Fixed | WidthScheme::Running => mv2,
+            WidthScheme::PoleMass => mv2 / (1.0 + (self.widthv / self.mv).powi(2)),
+        };
+        (s0, self.propagator_denominator(s0))
+    }
+    /// Compute the derivative of `propagator_denominator` with respect to `s`.
+    pub(super) fn propagator_denominator_derivative(&self, s: f64) -> f64 {
+        let mv2 = self.mv * self.mv;
+        match self.width_scheme {
+            WidthScheme::Fixed => 2.0 * (s - mv2),
+            WidthScheme::Running => {
+                let q = s.sqrt();
+                let h = 1e-6 * q;
+                let width = self.running_width(q);
+                let dwidth = (self.running_width(q + h) - self.running_width(q - h)) / (2.0 * h);
+                2.0 * (s - mv2) + width * width + q * width * dwidth
+            }
+            WidthScheme::PoleMass => {
+                let r2 = 1.0 / (1.0 + (self.widthv / self.mv).powi(2));
+                2.0 * (s - mv2 * r2)
+            }
+        }
+    }
+}
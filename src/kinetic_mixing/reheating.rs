@@ -0,0 +1,492 @@
+use super::instrumentation::{CounterHandle, Counters, SolverStatistics};
+use super::relic_result::{FreezeOutTrajectory, RelicResult};
+use super::KineticMixing;
+use crate::cosmology::{hubble_rate_from_energy_density, Cosmology};
+use crate::error::{check_solution, ModelError};
+use cyphus_diffeq::prelude::*;
+use cyphus_specfun::bessel::CylBesselK;
+use haliax_constants::prelude::*;
+use ndarray::prelude::*;
+use std::f64::consts::PI;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Parameters of a reheating period driven by the decay of the inflaton (or
+/// a modulus) `phi` into radiation and, with branching ratio `br_dm`, into
+/// chi + chibar.
+#[derive(Clone, Copy, Debug)]
+pub struct Reheating {
+    /// Reheating temperature, defined through `Gamma_phi = H(T_RH)` in a
+    /// universe dominated by the radiation of the bath.
+    pub t_rh: f64,
+    /// Mass of the decaying field.
+    pub mphi: f64,
+    /// Branching ratio of `phi -> chi + chibar`.
+    pub br_dm: f64,
+    /// Hubble rate at the start of the integration in units of `Gamma_phi`.
+    pub hubble_init: f64,
+}
+
+impl Reheating {
+    pub fn new(t_rh: f64, mphi: f64, br_dm: f64) -> Reheating {
+        Reheating {
+            t_rh,
+            mphi,
+            br_dm,
+            hubble_init: 1e6,
+        }
+    }
+    /// Compute the decay width of `phi` for the degrees of freedom of the
+    /// bath of `cosmology`.
+    pub fn width(&self, cosmology: &dyn Cosmology) -> f64 {
+        hubble_rate_from_energy_density(cosmology.bath_energy_density(self.t_rh))
+    }
+}
+
+/// Relative tolerance of the temperatures found by `invert_temperature`.
+const TEMPERATURE_RELTOL: f64 = 1e-12;
+/// Largest number of steps taken by `invert_temperature`, both to bracket the
+/// temperature and to locate it.
+const TEMPERATURE_MAX_ITER: usize = 200;
+
+/// Find the temperature at which the increasing function `f` of the
+/// temperature equals `target`, starting from `guess`.
+///
+/// The temperature is bracketed by doubling and halving `guess` and then
+/// located with the Illinois variant of regula falsi in the logarithms. This
+/// takes a few steps where `f` is close to a power law and still converges
+/// onto the step of a table with a sharp QCD transition, where `f` jumps over
+/// `target`.
+fn invert_temperature<F>(
+    f: F,
+    target: f64,
+    guess: f64,
+    quantity: &'static str,
+) -> Result<f64, ModelError>
+where
+    F: Fn(f64) -> f64,
+{
+    let fail = || ModelError::TemperatureNotFound {
+        quantity,
+        value: target,
+    };
+    if !(target > 0.0 && target.is_finite() && guess > 0.0 && guess.is_finite()) {
+        return Err(fail());
+    }
+    let logtarget = target.ln();
+    let g = |logt: f64| f(logt.exp()).ln() - logtarget;
+
+    let (mut a, mut b) = (guess.ln(), guess.ln());
+    let (mut fa, mut fb) = (g(a), g(b));
+    for _ in 0..TEMPERATURE_MAX_ITER {
+        if fa.is_nan() || fb.is_nan() {
+            return Err(fail());
+        }
+        if fa > 0.0 {
+            b = a;
+            fb = fa;
+            a -= std::f64::consts::LN_2;
+            fa = g(a);
+        } else if fb < 0.0 {
+            a = b;
+            fa = fb;
+            b += std::f64::consts::LN_2;
+            fb = g(b);
+        } else {
+            break;
+        }
+    }
+    if !(fa <= 0.0 && fb >= 0.0) {
+        return Err(fail());
+    }
+
+    // Endpoint replaced in the previous step: -1 for `a`, 1 for `b`.
+    let mut side = 0;
+    for _ in 0..TEMPERATURE_MAX_ITER {
+        if fa == 0.0 {
+            return Ok(a.exp());
+        }
+        if fb == 0.0 {
+            return Ok(b.exp());
+        }
+        if b - a <= TEMPERATURE_RELTOL {
+            return Ok((0.5 * (a + b)).exp());
+        }
+        let c = (a * fb - b * fa) / (fb - fa);
+        let fc = g(c);
+        if fc.is_nan() {
+            return Err(fail());
+        }
+        if fc.abs() <= TEMPERATURE_RELTOL {
+            return Ok(c.exp());
+        }
+        if fc > 0.0 {
+            b = c;
+            fb = fc;
+            if side == 1 {
+                fa *= 0.5;
+            }
+            side = 1;
+        } else {
+            a = c;
+            fa = fc;
+            if side == -1 {
+                fb *= 0.5;
+            }
+            side = -1;
+        }
+    }
+    Err(fail())
+}
+
+/// Compute the temperature at which the bath of `cosmology` has the entropy
+/// density `s`.
+pub(super) fn temperature_from_entropy_density(
+    cosmology: &dyn Cosmology,
+    s: f64,
+) -> Result<f64, ModelError> {
+    let guess = (45.0 * s / (2.0 * PI * PI * 106.75)).cbrt();
+    invert_temperature(
+        |temp| cosmology.entropy_density(temp),
+        s,
+        guess,
+        "entropy density",
+    )
+}
+
+/// Compute the temperature at which the bath of `cosmology` has the energy
+/// density `rho`.
+pub(super) fn temperature_from_energy_density(
+    cosmology: &dyn Cosmology,
+    rho: f64,
+) -> Result<f64, ModelError> {
+    let guess = (30.0 * rho / (PI * PI * 106.75)).powf(0.25);
+    invert_temperature(
+        |temp| cosmology.bath_energy_density(temp),
+        rho,
+        guess,
+        "energy density",
+    )
+}
+
+/// Compute the mean energy of a Maxwell-Boltzmann species of mass `mass` in
+/// kinetic equilibrium at `temp`.
+pub(super) fn mean_energy(mass: f64, temp: f64) -> f64 {
+    let z = mass / temp;
+    mass * z.cyl_bessel_k1_scaled() / z.cyl_bessel_kn_scaled(2) + 3.0 * temp
+}
+
+/// Compute `d log(f) / d log(T)` at `temp` by central differences. Used for
+/// the energy and entropy densities of the bath, whose degrees of freedom
+/// are only known as tables.
+pub(super) fn log_derivative<F>(f: F, temp: f64) -> f64
+where
+    F: Fn(f64) -> f64,
+{
+    let h: f64 = 1e-4;
+    (f(temp * h.exp()).ln() - f(temp * (-h).exp()).ln()) / (2.0 * h)
+}
+
+/// Compute `d log(n_eq) / d log(T) = 3 + z K1(z) / K2(z)` with `z = mass / T`
+/// for a Maxwell-Boltzmann species of mass `mass` at `temp`.
+pub(super) fn dlogneq_dlogt(mass: f64, temp: f64) -> f64 {
+    let z = mass / temp;
+    3.0 + z * z.cyl_bessel_k1_scaled() / z.cyl_bessel_kn_scaled(2)
+}
+
+/// Compute the Jacobian of `f` with respect to `w` using central differences.
+#[cfg(test)]
+pub(super) fn finite_difference_jacobian<F>(f: F, mut dw: ArrayViewMut2<f64>, w: ArrayView1<f64>)
+where
+    F: Fn(ArrayViewMut1<f64>, ArrayView1<f64>),
+{
+    let n = w.len();
+    let mut wp = w.to_owned();
+    let mut fp = Array1::<f64>::zeros(n);
+    let mut fm = Array1::<f64>::zeros(n);
+    for j in 0..n {
+        let h = 1e-6 * w[j].abs().max(1.0);
+        wp[j] = w[j] + h;
+        f(fp.view_mut(), wp.view());
+        wp[j] = w[j] - h;
+        f(fm.view_mut(), wp.view());
+        wp[j] = w[j];
+        for i in 0..n {
+            dw[[i, j]] = (fp[i] - fm[i]) / (2.0 * h);
+        }
+    }
+}
+
+/// Coupled evolution of `phi`, radiation and chi as a function of the
+/// logarithm of the scale factor. The unknowns are
+/// `[log(rho_phi a^3 / rho_i), log(s a^3 mx / rho_i), log(n_chi a^3 mx / rho_i)]`
+/// with `rho_i` the initial energy density of `phi` and `s` the entropy
+/// density of the bath, which stays conserved as its degrees of freedom
+/// change. The density of chibar equals `n_chi`.
+#[derive(Clone)]
+struct ReheatingSystem {
+    km: KineticMixing,
+    rh: Reheating,
+    gamma: f64,
+    rho_i: f64,
+}
+
+impl ReheatingSystem {
+    /// Compute `(H, T, rho_phi, s, n_chi)` for the state `w` at `loga`.
+    fn state(
+        &self,
+        w: ArrayView1<f64>,
+        loga: f64,
+    ) -> Result<(f64, f64, f64, f64, f64), ModelError> {
+        let mx = self.km.mx;
+        let cosmology = self.km.cosmology.as_ref();
+        let rhophi = self.rho_i * (w[0] - 3.0 * loga).exp();
+        let s = self.rho_i / mx * (w[1] - 3.0 * loga).exp();
+        let n = self.rho_i / mx * (w[2] - 3.0 * loga).exp();
+        let temp = temperature_from_entropy_density(cosmology, s)?;
+        let rhor = cosmology.bath_energy_density(temp);
+        // chi and chibar both contribute to the energy density.
+        let rhochi = 2.0 * mean_energy(mx, temp) * n;
+        let hubble = hubble_rate_from_energy_density(rhophi + rhor + rhochi);
+        Ok((hubble, temp, rhophi, s, n))
+    }
+    fn dudt(&self, mut dw: ArrayViewMut1<f64>, w: ArrayView1<f64>, loga: f64) {
+        let mx = self.km.mx;
+        // A failed inversion is reported by `relic_density_reheating` once
+        // the solution stops.
+        let (hubble, temp, rhophi, s, n) = match self.state(w, loga) {
+            Ok(state) => state,
+            Err(_) => {
+                dw.fill(f64::NAN);
+                return;
+            }
+        };
+        let nequil = self.km.neq(temp);
+        let sigmav = self.km.thermal_cross_section(mx / temp);
+        let energy = mean_energy(mx, temp);
+
+        let ann = sigmav * (n * n - nequil * nequil);
+        let decay = self.gamma * rhophi;
+
+        dw[0] = -self.gamma / hubble;
+        // The heat released into the bath raises its entropy, T ds = dq.
+        dw[1] = ((1.0 - self.rh.br_dm) * decay + 2.0 * energy * ann) / (hubble * temp * s);
+        dw[2] = (-ann + self.rh.br_dm * decay / self.rh.mphi) / (hubble * n);
+    }
+    /// Compute the Jacobian of `dudt`. Only `w[1]` changes the temperature,
+    /// through `d log(T) / d w[1] = 1 / (d log(s) / d log(T))`. The thermal
+    /// cross section and the mean energy of chi are held fixed and `n_eq` is
+    /// differentiated as a Maxwell-Boltzmann density, which is all the Newton
+    /// iteration of Radau5 needs, so no thermal averages beyond that of
+    /// `dudt` are computed.
+    fn dfdu(&self, mut dw: ArrayViewMut2<f64>, w: ArrayView1<f64>, loga: f64) {
+        let mx = self.km.mx;
+        let cosmology = self.km.cosmology.as_ref();
+        let (hubble, temp, rhophi, s, n) = match self.state(w, loga) {
+            Ok(state) => state,
+            Err(_) => {
+                dw.fill(f64::NAN);
+                return;
+            }
+        };
+        let nequil = self.km.neq(temp);
+        let sigmav = self.km.thermal_cross_section(mx / temp);
+        let energy = mean_energy(mx, temp);
+        let (br, gamma) = (self.rh.br_dm, self.gamma);
+
+        let dlogt = 1.0 / log_derivative(|t| cosmology.entropy_density(t), temp);
+        let rhor = cosmology.bath_energy_density(temp);
+        let rhochi = 2.0 * energy * n;
+        let rho = rhophi + rhor + rhochi;
+        let drhor = rhor * log_derivative(|t| cosmology.bath_energy_density(t), temp);
+        // d log(H) / d w[j]
+        let dlogh = [
+            0.5 * rhophi / rho,
+            0.5 * drhor * dlogt / rho,
+            0.5 * rhochi / rho,
+        ];
+
+        let ann = sigmav * (n * n - nequil * nequil);
+        let dann = [
+            0.0,
+            -2.0 * sigmav * nequil * nequil * dlogneq_dlogt(mx, temp) * dlogt,
+            2.0 * sigmav * n * n,
+        ];
+        let decay = gamma * rhophi;
+
+        let f0 = -gamma / hubble;
+        let f1 = ((1.0 - br) * decay + 2.0 * energy * ann) / (hubble * temp * s);
+        let f2 = (-ann + br * decay / self.rh.mphi) / (hubble * n);
+        for j in 0..3 {
+            let ddecay = if j == 0 { decay } else { 0.0 };
+            dw[[0, j]] = -f0 * dlogh[j];
+            let dlogts = if j == 1 { dlogt + 1.0 } else { 0.0 };
+            dw[[1, j]] = ((1.0 - br) * ddecay + 2.0 * energy * dann[j]) / (hubble * temp * s)
+                - f1 * (dlogh[j] + dlogts);
+            let dlogn = if j == 2 { 1.0 } else { 0.0 };
+            dw[[2, j]] =
+                (-dann[j] + br * ddecay / self.rh.mphi) / (hubble * n) - f2 * (dlogh[j] + dlogn);
+        }
+    }
+}
+
+impl KineticMixing {
+    fn reheating_system(&self, rh: &Reheating) -> ReheatingSystem {
+        let gamma = rh.width(self.cosmology.as_ref());
+        let hubble_i = rh.hubble_init * gamma;
+        ReheatingSystem {
+            km: self.clone(),
+            rh: *rh,
+            gamma,
+            rho_i: 3.0 * (M_PLANK * hubble_i).powi(2) / (8.0 * PI),
+        }
+    }
+    /// Compute the temperature at which the reheating integration stops.
+    fn reheating_temp_end(&self, rh: &Reheating) -> f64 {
+        rh.t_rh.min(self.mx) / 1000.0
+    }
+    /// Compute `log(a / a_i)` at which the reheating integration stops.
+    fn reheating_loga_end(&self, rh: &Reheating) -> f64 {
+        // phi decays once H ~ Gamma, with H ~ a^(-3/2) during matter domination.
+        let loga_rh = 2.0 / 3.0 * rh.hubble_init.ln();
+        loga_rh + (rh.t_rh / self.reheating_temp_end(rh)).ln() + 2.0
+    }
+    /// Solve for the evolution of `phi`, radiation and chi during reheating.
+    /// The solution contains
+    /// `[log(rho_phi a^3 / rho_i), log(s a^3 mx / rho_i), log(n_chi a^3 mx / rho_i)]`
+    /// as a function of `log(a / a_i)`, with `rho_i` the initial energy density
+    /// of `phi` and `s` the entropy density of the bath.
+    ///
+    /// Radiation starts on the attractor `rho_r = 2/5 (1 - br) Gamma / H rho_phi`.
+    /// chi starts with the abundance the bath produces within one Hubble time,
+    /// `n_eq min(1, n_eq <sigma v> / H)`, i.e. in chemical equilibrium if it
+    /// annihilates faster than the universe expands. The integration stops
+    /// once the temperature has dropped a factor of 1000 below the smaller of
+    /// `T_RH` and `mx`.
+    ///
+    /// Fails if the initial temperature of the bath cannot be found.
+    pub fn solve_reheating(&self, rh: &Reheating) -> Result<OdeSolution, ModelError> {
+        Ok(self.solve_reheating_with_statistics(rh)?.0)
+    }
+    /// Solve for the evolution during reheating as `solve_reheating` and
+    /// report the work done by the integrators.
+    pub fn solve_reheating_with_statistics(
+        &self,
+        rh: &Reheating,
+    ) -> Result<(OdeSolution, SolverStatistics), ModelError> {
+        let start = Instant::now();
+        let counters = Arc::new(Counters::default());
+        let mut sys = self.reheating_system(rh);
+        sys.km.counters = CounterHandle::shared(&counters);
+
+        let rhor = 0.4 * (1.0 - rh.br_dm) * sys.rho_i / rh.hubble_init;
+        let temp = temperature_from_energy_density(self.cosmology.as_ref(), rhor)?;
+        let s = self.cosmology.entropy_density(temp);
+        let nequil = self.neq(temp);
+        let rate = nequil * self.thermal_cross_section(self.mx / temp);
+        let n = (nequil * (rate / (rh.hubble_init * sys.gamma)).min(1.0)).max(1e-30 * s);
+        let uinit = array![
+            0.0,
+            (s * self.mx / sys.rho_i).ln(),
+            (n * self.mx / sys.rho_i).ln()
+        ];
+
+        let tspan = (0.0, self.reheating_loga_end(rh));
+
+        let dudt =
+            |mut dw: ArrayViewMut1<f64>, w: ArrayView1<f64>, loga: f64, p: &ReheatingSystem| {
+                p.km.counters.count_rhs();
+                p.dudt(dw.view_mut(), w.view(), loga)
+            };
+        let dfdu =
+            |mut dw: ArrayViewMut2<f64>, w: ArrayView1<f64>, loga: f64, p: &ReheatingSystem| {
+                p.km.counters.count_jacobian();
+                p.dfdu(dw.view_mut(), w.view(), loga)
+            };
+
+        let mut integrator = OdeIntegratorBuilder::default(&dudt, uinit, tspan, Radau5, sys)
+            .dfdu(&dfdu)
+            .reltol(self.ode_reltol)
+            .abstol(self.ode_abstol)
+            .build();
+
+        integrator.integrate();
+        let sol = integrator.sol;
+        let stats = counters.statistics(sol.ts.len().saturating_sub(1), start.elapsed());
+        Ok((sol, stats))
+    }
+    /// Compute the relic density when freeze-out or freeze-in happens during
+    /// reheating. Entropy is not conserved while `phi` decays, so the comoving
+    /// abundance is only formed once `phi` has decayed.
+    ///
+    /// The trajectory of the result is sampled at the steps of the solver in
+    /// `log(a)` and its ratio of the annihilation rate to the Hubble rate
+    /// includes the energy density of `phi`.
+    ///
+    /// Fails if the temperature of the bath cannot be found, the integration
+    /// stops early or the solution becomes non-finite.
+    pub fn relic_density_reheating(&self, rh: &Reheating) -> Result<RelicResult, ModelError> {
+        let sys = self.reheating_system(rh);
+        let (sol, stats) = self.solve_reheating_with_statistics(rh)?;
+        let states = sol
+            .ts
+            .iter()
+            .zip(sol.us.iter())
+            .filter(|(_, u)| u.iter().all(|v| v.is_finite()))
+            .map(|(&loga, u)| sys.state(u.view(), loga))
+            .collect::<Result<Vec<_>, _>>()?;
+        let x_of = |loga: f64, u: ArrayView1<f64>| {
+            sys.state(u, loga)
+                .map_or(f64::NAN, |state| self.mx / state.1)
+        };
+        let x_end = self.mx / self.reheating_temp_end(rh);
+        check_solution(&sol, self.reheating_loga_end(rh), x_end, x_of)?;
+
+        let mut trajectory = FreezeOutTrajectory {
+            x: Vec::with_capacity(states.len()),
+            y: Vec::with_capacity(states.len()),
+            yeq: Vec::with_capacity(states.len()),
+            gamma_over_hubble: Vec::with_capacity(states.len()),
+        };
+        for &(hubble, temp, _, s, n) in states.iter() {
+            let nequil = self.neq(temp);
+            trajectory.x.push(self.mx / temp);
+            trajectory.y.push(n / s);
+            trajectory.yeq.push(nequil / s);
+            trajectory
+                .gamma_over_hubble
+                .push(nequil * self.thermal_cross_section(self.mx / temp) / hubble);
+        }
+        Ok(self.relic_result_from_trajectory(trajectory, stats))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reheating_jacobian() {
+        let km = KineticMixing::new(1e3, 1e4, 1.0, 1e-1).unwrap();
+        let mut rh = Reheating::new(10.0, 1e7, 1e-3);
+        rh.hubble_init = 1e10;
+        let sys = km.reheating_system(&rh);
+        let sol = km.solve_reheating(&rh).unwrap();
+        // The columns of phi and chi do not change the temperature and are
+        // exact; the column of the entropy holds <sigma v> fixed.
+        for i in [sol.ts.len() / 4, sol.ts.len() / 2].iter() {
+            let (w, loga) = (sol.us[*i].view(), sol.ts[*i]);
+            let mut analytic = Array2::<f64>::zeros((3, 3));
+            let mut numerical = Array2::<f64>::zeros((3, 3));
+            sys.dfdu(analytic.view_mut(), w, loga);
+            let f = |df: ArrayViewMut1<f64>, u: ArrayView1<f64>| sys.dudt(df, u, loga);
+            finite_difference_jacobian(f, numerical.view_mut(), w);
+            for r in 0..3 {
+                let scale = numerical.row(r).iter().fold(0.0f64, |m, d| m.max(d.abs()));
+                for &c in [0, 2].iter() {
+                    assert!((analytic[[r, c]] - numerical[[r, c]]).abs() <= 1e-5 * scale);
+                }
+            }
+        }
+    }
+}
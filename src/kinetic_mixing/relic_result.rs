@@ -0,0 +1,128 @@
+use super::instrumentation::SolverStatistics;
+use super::{KineticMixing, KineticMixingFinalStates, ANNIHILATION_CHANNELS};
+use cyphus_diffeq::prelude::*;
+use haliax_constants::prelude::*;
+
+/// Dark matter density measured by Planck, `Omega h^2`.
+pub const PLANCK_OMEGA_H2: f64 = 0.120;
+
+/// Abundance of chi along the solution of the Boltzmann equation.
+#[derive(Clone, Debug)]
+pub struct FreezeOutTrajectory {
+    /// Values of `x = mx / T` at the steps of the solver.
+    pub x: Vec<f64>,
+    /// Comoving abundance `Y = n / s` of chi (not its logarithm).
+    pub y: Vec<f64>,
+    /// Equilibrium abundance of chi.
+    pub yeq: Vec<f64>,
+    /// Ratio of the annihilation rate `n_eq <sigma v>` to the Hubble rate.
+    pub gamma_over_hubble: Vec<f64>,
+}
+
+/// Relic density of chi together with freeze-out diagnostics.
+#[derive(Clone, Debug)]
+pub struct RelicResult {
+    /// Relic density `Omega h^2` of chi alone. The density of chibar is the
+    /// same.
+    pub omega: f64,
+    /// Relic density `Omega h^2` of chi and chibar, `2 omega`.
+    pub omega_total: f64,
+    /// Comoving abundance after freeze-out.
+    pub yinf: f64,
+    /// Freeze-out point, where `n_eq <sigma v> = H`. It is clamped to the
+    /// integration range if the rates never cross.
+    pub xf: f64,
+    /// Freeze-out temperature, `mx / xf`.
+    pub tf: f64,
+    /// Ratio of `omega_total` to `PLANCK_OMEGA_H2`.
+    pub planck_ratio: f64,
+    /// Thermally averaged cross section at `xf`.
+    pub sigmav_f: f64,
+    /// Fractions of the annihilation cross section at `xf` going into each
+    /// final state, using Maxwell-Boltzmann statistics. Bound-state formation
+    /// is not included.
+    pub channel_fractions: Vec<(KineticMixingFinalStates, f64)>,
+    /// Solution of the Boltzmann equation.
+    pub trajectory: FreezeOutTrajectory,
+    /// Work done by the integrators while solving the Boltzmann equation.
+    pub statistics: SolverStatistics,
+}
+
+impl KineticMixing {
+    /// Compute the trajectory of the abundance from a solution of the
+    /// Boltzmann equation in `log(x)` and `log(Y)`.
+    fn freeze_out_trajectory(&self, sol: &OdeSolution) -> FreezeOutTrajectory {
+        let x: Vec<f64> = sol.ts.iter().map(|logx| logx.exp()).collect();
+        let y = sol.us.iter().map(|u| u[0].exp()).collect();
+        let mut yeq = Vec::with_capacity(x.len());
+        let mut gamma_over_hubble = Vec::with_capacity(x.len());
+        for &xi in x.iter() {
+            let temp = self.mx / xi;
+            let neq = self.neq(temp);
+            yeq.push(neq / self.cosmology.entropy_density(temp));
+            gamma_over_hubble
+                .push(neq * self.thermal_cross_section(xi) / self.cosmology.hubble_rate(temp));
+        }
+        FreezeOutTrajectory {
+            x,
+            y,
+            yeq,
+            gamma_over_hubble,
+        }
+    }
+    /// Collect the relic density and freeze-out diagnostics from a solution of
+    /// the Boltzmann equation.
+    pub(super) fn relic_result(
+        &self,
+        sol: &OdeSolution,
+        statistics: SolverStatistics,
+    ) -> RelicResult {
+        self.relic_result_from_trajectory(self.freeze_out_trajectory(sol), statistics)
+    }
+    /// Collect the relic density and freeze-out diagnostics from the
+    /// trajectory of the abundance, which ends after freeze-out.
+    pub(super) fn relic_result_from_trajectory(
+        &self,
+        trajectory: FreezeOutTrajectory,
+        statistics: SolverStatistics,
+    ) -> RelicResult {
+        let yinf = *trajectory.y.last().unwrap();
+        let omega = yinf * self.mx * S_TODAY / RHO_CRIT;
+
+        // Interpolate log(Gamma / H) linearly in log(x) across the crossing.
+        let (xs, rs) = (&trajectory.x, &trajectory.gamma_over_hubble);
+        let xf = match (1..xs.len()).find(|&i| rs[i - 1] >= 1.0 && rs[i] < 1.0) {
+            Some(i) => {
+                let (l0, l1) = (rs[i - 1].ln(), rs[i].ln());
+                let f = l0 / (l0 - l1);
+                (xs[i - 1].ln() + f * (xs[i].ln() - xs[i - 1].ln())).exp()
+            }
+            None if rs[0] < 1.0 => xs[0],
+            None => *xs.last().unwrap(),
+        };
+
+        let partials: Vec<f64> = ANNIHILATION_CHANNELS
+            .iter()
+            .map(|fs| self.thermal_partial_cross_section(xf, *fs))
+            .collect();
+        let total: f64 = partials.iter().sum();
+        let channel_fractions = ANNIHILATION_CHANNELS
+            .iter()
+            .zip(partials.iter())
+            .map(|(fs, p)| (*fs, if total > 0.0 { p / total } else { 0.0 }))
+            .collect();
+
+        RelicResult {
+            omega,
+            omega_total: 2.0 * omega,
+            yinf,
+            xf,
+            tf: self.mx / xf,
+            planck_ratio: 2.0 * omega / PLANCK_OMEGA_H2,
+            sigmav_f: self.thermal_cross_section(xf),
+            channel_fractions,
+            trajectory,
+            statistics,
+        }
+    }
+}
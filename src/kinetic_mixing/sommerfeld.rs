@@ -0,0 +1,226 @@
+use super::KineticMixing;
+use cyphus_integration::prelude::*;
+use once_cell::sync::OnceCell;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// Largest number of Runge-Kutta steps in `sommerfeld_factor_numerical`.
+/// Beyond this the accumulated error of the integration exceeds `1e-3`.
+const SOMMERFELD_MAX_STEPS: usize = 1_000_000;
+/// Size of the Born correction below which `sommerfeld_factor_numerical`
+/// uses the Born approximation, whose error is of order its square.
+const SOMMERFELD_BORN_LIMIT: f64 = 1e-2;
+/// Ratio of the wavelength to the range of the potential below which
+/// `sommerfeld_factor_numerical` uses the Coulomb factor. The error of the
+/// Coulomb factor is then below `1e-3`.
+const SOMMERFELD_COULOMB_LIMIT: f64 = 1e-2;
+/// Smallest relative velocity in the table of `sommerfeld_factor_tabulated`.
+/// Below it the factor is computed directly.
+const SOMMERFELD_TABLE_VMIN: f64 = 1e-6;
+/// Points per decade of the relative velocity in the table. The error of
+/// the interpolation is below `1e-3` away from bound-state thresholds.
+const SOMMERFELD_TABLE_DENSITY: f64 = 40.0;
+
+/// Treatment of the Sommerfeld enhancement from `V` exchange between the
+/// incoming chi and chibar.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sommerfeld {
+    /// No enhancement.
+    Off,
+    /// Closed form obtained by replacing the Yukawa potential with a Hulthen
+    /// potential.
+    Hulthen,
+    /// Numerical solution of the s-wave Schrodinger equation with the
+    /// Yukawa potential.
+    Numerical,
+}
+
+/// Numerical Sommerfeld factor tabulated on a logarithmic grid of relative
+/// velocities, as `log(S)` against `log(v)`.
+#[derive(Debug)]
+struct SommerfeldTable {
+    logv: Vec<f64>,
+    logs: Vec<f64>,
+}
+
+/// Lazily computed `SommerfeldTable` of a model. Clones share the table; it
+/// is reset whenever the masses or the dark coupling change.
+#[derive(Clone, Debug, Default)]
+pub(super) struct SommerfeldCache(Arc<OnceCell<SommerfeldTable>>);
+
+impl KineticMixing {
+    /// Compute the dark fine-structure constant `gvxx^2 / (4 pi)`.
+    pub fn alpha_dark(&self) -> f64 {
+        self.gvxx * self.gvxx / (4.0 * PI)
+    }
+    /// Compute the s-wave Sommerfeld factor for the attractive potential
+    /// `-alpha_dark exp(-mv r) / r` at relative velocity `v`, using the
+    /// method selected by `self.sommerfeld`.
+    pub fn sommerfeld_factor(&self, v: f64) -> f64 {
+        match self.sommerfeld {
+            Sommerfeld::Off => 1.0,
+            Sommerfeld::Hulthen => self.sommerfeld_factor_hulthen(v),
+            Sommerfeld::Numerical => self.sommerfeld_factor_tabulated(v),
+        }
+    }
+    /// Compute the Sommerfeld factor in the Hulthen approximation,
+    /// `S = (pi / a) sinh(2 pi a c) / (cosh(2 pi a c) - cos(2 pi sqrt(c - a^2 c^2)))`
+    /// with `a = v / (2 alpha)` and `c = 6 alpha mx / (pi^2 mv)`.
+    pub fn sommerfeld_factor_hulthen(&self, v: f64) -> f64 {
+        let alpha = self.alpha_dark();
+        let a = v / (2.0 * alpha);
+        let c = 6.0 * alpha * self.mx / (PI * PI * self.mv);
+        let d = c - (a * c).powi(2);
+        let x = 2.0 * PI * a * c;
+        // Divide numerator and denominator by exp(x) / 2 to avoid overflow.
+        // Above the last bound state the cosine continues into a cosh.
+        let den = if d >= 0.0 {
+            1.0 + (-2.0 * x).exp() - 2.0 * (2.0 * PI * d.sqrt()).cos() * (-x).exp()
+        } else {
+            let y = 2.0 * PI * (-d).sqrt();
+            1.0 + (-2.0 * x).exp() - (y - x).exp() - (-y - x).exp()
+        };
+        PI / a * -(-2.0 * x).exp_m1() / den
+    }
+    /// Compute the Sommerfeld factor by integrating the s-wave radial
+    /// Schrodinger equation outwards with a fourth-order Runge-Kutta method.
+    ///
+    /// In terms of `rho = alpha mu r`, with `mu = mx / 2` the reduced mass, the
+    /// equation reads `u'' + (ev^2 + 2 exp(-ephi rho) / rho) u = 0` with
+    /// `ev = v / alpha` and `ephi = 2 mv / (alpha mx)`. Starting from
+    /// `u(0) = 0, u'(0) = 1`, the factor is `1 / (u'^2 + ev^2 u^2)` once the
+    /// potential is negligible against `ev^2`.
+    ///
+    /// Outside the range where the integration is both accurate and cheap,
+    /// limiting forms are used instead:
+    ///
+    /// - the first Born approximation, `1 + 2 / ev atan(2 ev / ephi)`, when
+    ///   the correction is below `SOMMERFELD_BORN_LIMIT`,
+    /// - the Coulomb factor, `zeta / (1 - exp(-zeta))` with `zeta = 2 pi / ev`,
+    ///   when the range of the potential exceeds the wavelength by a factor
+    ///   of `1 / SOMMERFELD_COULOMB_LIMIT`, i.e. `ephi < 0.01 ev`,
+    /// - the Hulthen approximation when the integration would need more than
+    ///   `SOMMERFELD_MAX_STEPS` steps. This happens only for `ephi < 1e-7`
+    ///   and `ev < 100 ephi`, where the potential has many bound states.
+    pub fn sommerfeld_factor_numerical(&self, v: f64) -> f64 {
+        let alpha = self.alpha_dark();
+        let ev = v / alpha;
+        let ephi = 2.0 * self.mv / (alpha * self.mx);
+
+        let born = 2.0 / ev * (2.0 * ev / ephi).atan();
+        if born < SOMMERFELD_BORN_LIMIT {
+            return 1.0 + born;
+        }
+        if ephi < SOMMERFELD_COULOMB_LIMIT * ev {
+            let zeta = 2.0 * PI / ev;
+            return zeta / -(-zeta).exp_m1();
+        }
+        // With steps of 0.05 local wavelengths, the number of steps is
+        // 20 int k drho with k <= ev + sqrt(2 / rho).
+        let rho_max = 30.0 / ephi;
+        let steps = 20.0 * (ev * rho_max + 2.0 * (2.0 * rho_max).sqrt());
+        if steps > SOMMERFELD_MAX_STEPS as f64 {
+            return self.sommerfeld_factor_hulthen(v);
+        }
+
+        let potential = |rho: f64| -> f64 { 2.0 * (-ephi * rho).exp() / rho };
+        let accel = |rho: f64, u: f64| -> f64 { -(ev * ev + potential(rho)) * u };
+
+        // Series solution near the origin, where u'' = -2 u / rho.
+        let mut rho = 1e-6;
+        let mut u = rho - rho * rho;
+        let mut du = 1.0 - 2.0 * rho;
+        for _ in 0..SOMMERFELD_MAX_STEPS {
+            let pot = potential(rho);
+            if rho >= rho_max || pot < 1e-6 * ev * ev {
+                break;
+            }
+            // Resolve the local wavelength.
+            let h = 0.05 / (ev * ev + pot).sqrt();
+            let (k1u, k1d) = (du, accel(rho, u));
+            let (k2u, k2d) = (du + 0.5 * h * k1d, accel(rho + 0.5 * h, u + 0.5 * h * k1u));
+            let (k3u, k3d) = (du + 0.5 * h * k2d, accel(rho + 0.5 * h, u + 0.5 * h * k2u));
+            let (k4u, k4d) = (du + h * k3d, accel(rho + h, u + h * k3u));
+            u += h / 6.0 * (k1u + 2.0 * k2u + 2.0 * k3u + k4u);
+            du += h / 6.0 * (k1d + 2.0 * k2d + 2.0 * k3d + k4d);
+            rho += h;
+        }
+        1.0 / (du * du + ev * ev * u * u)
+    }
+    /// Interpolate the Sommerfeld factor of `sommerfeld_factor_numerical`
+    /// from a table computed once per model, from `SOMMERFELD_TABLE_VMIN` up
+    /// to `v = 2`. Outside the table the factor is computed directly.
+    pub fn sommerfeld_factor_tabulated(&self, v: f64) -> f64 {
+        if !(SOMMERFELD_TABLE_VMIN..=2.0).contains(&v) {
+            return self.sommerfeld_factor_numerical(v);
+        }
+        let table = self.sommerfeld_table.0.get_or_init(|| {
+            let n = ((2.0 / SOMMERFELD_TABLE_VMIN).log10() * SOMMERFELD_TABLE_DENSITY).ceil();
+            let logv: Vec<f64> = (0..=n as usize)
+                .map(|i| {
+                    SOMMERFELD_TABLE_VMIN.ln() + (2.0 / SOMMERFELD_TABLE_VMIN).ln() * i as f64 / n
+                })
+                .collect();
+            let logs = logv
+                .iter()
+                .map(|lv| self.sommerfeld_factor_numerical(lv.exp()).ln())
+                .collect();
+            SommerfeldTable { logv, logs }
+        });
+        let logv = v.ln();
+        let i = (table.logv.partition_point(|&t| t <= logv) - 1).min(table.logv.len() - 2);
+        let f = (logv - table.logv[i]) / (table.logv[i + 1] - table.logv[i]);
+        (table.logs[i] + f * (table.logs[i + 1] - table.logs[i])).exp()
+    }
+    /// Compute the Sommerfeld factor at the relative velocity corresponding
+    /// to the center-of-mass energy `cme`, using `s = 4 mx^2 / (1 - v^2 / 4)`.
+    pub(super) fn sommerfeld_factor_at_cme(&self, cme: f64) -> f64 {
+        if self.sommerfeld == Sommerfeld::Off || cme <= 2.0 * self.mx {
+            return 1.0;
+        }
+        let v = 2.0 * (1.0 - 4.0 * self.mx * self.mx / (cme * cme)).sqrt();
+        self.sommerfeld_factor(v)
+    }
+    /// Compute the annihilation cross section including the Sommerfeld
+    /// factor. The factor is applied to the full cross section, which is
+    /// dominated by the s-wave.
+    pub(super) fn enhanced_annihilation_cross_section(&self, cme: f64) -> f64 {
+        self.annihilation_cross_section(cme) * self.sommerfeld_factor_at_cme(cme)
+    }
+    /// Compute `sigma v` for chi + chibar -> anything at relative velocity
+    /// `v`, including the Sommerfeld factor.
+    pub fn sigma_v(&self, v: f64) -> f64 {
+        let cme = 2.0 * self.mx / (1.0 - 0.25 * v * v).sqrt();
+        self.enhanced_annihilation_cross_section(cme) * v
+    }
+    /// Compute `<sigma v>` in a halo today, averaging `sigma_v` over the
+    /// Maxwellian distribution of relative velocities of chi and chibar whose
+    /// individual velocities have most probable speed `v0`.
+    pub fn late_time_cross_section(&self, v0: f64) -> f64 {
+        self.maxwellian_average(|v| self.sigma_v(v), v0)
+    }
+    /// Average `f(v)` over the Maxwellian distribution of relative velocities
+    /// `sqrt(2 / pi) v^2 / v0^3 exp(-v^2 / (2 v0^2))`.
+    pub(super) fn maxwellian_average<F>(&self, f: F, v0: f64) -> f64
+    where
+        F: Fn(f64) -> f64,
+    {
+        self.maxwellian_average_with_error(f, v0).val
+    }
+    /// Average `f(v)` like `maxwellian_average`, together with the error
+    /// estimate of the integration.
+    pub(super) fn maxwellian_average_with_error<F>(&self, f: F, v0: f64) -> IntegrationResult
+    where
+        F: Fn(f64) -> f64,
+    {
+        let gk = GaussKronrodIntegratorBuilder::default()
+            .epsabs(0.0)
+            .epsrel(1e-6)
+            .limit(200)
+            .build();
+        let integrand = |v: f64| -> f64 {
+            (2.0 / PI).sqrt() * v * v / v0.powi(3) * (-0.5 * (v / v0).powi(2)).exp() * f(v)
+        };
+        gk.integrate(integrand, 0.0, (20.0 * v0).min(1.99))
+    }
+}
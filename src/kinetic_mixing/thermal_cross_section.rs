@@ -1,20 +1,300 @@
-use super::KineticMixing;
+use super::{KineticMixing, KineticMixingFinalStates};
+use crate::error::{check_parameter, ModelError};
+use crate::statistics::Statistics;
+use cyphus_integration::prelude::*;
 use cyphus_specfun::bessel::CylBesselK;
+use std::f64::consts::PI;
+use std::time::Instant;
+
+/// Resonances with `widthv / mv` below this value are integrated
+/// analytically in the narrow-width approximation.
+const NARROW_WIDTH_RATIO: f64 = 1e-5;
+
+/// Half-width, in units of `widthv`, of the window around a narrow resonance
+/// that is integrated analytically. This is also the outermost breakpoint
+/// placed around the resonance.
+pub(super) const NARROW_WIDTH_WINDOW: f64 = 100.0;
+
+/// Thermally averaged cross section together with the error estimate of the
+/// Gauss-Kronrod integration.
+#[derive(Clone, Copy, Debug)]
+pub struct ThermalAverage {
+    /// Value of the thermally averaged cross section.
+    pub val: f64,
+    /// Estimated absolute error.
+    pub err: f64,
+    /// Whether the estimated error satisfies the requested tolerance.
+    pub converged: bool,
+}
+
+impl ThermalAverage {
+    fn new(val: f64, err: f64, epsrel: f64) -> ThermalAverage {
+        ThermalAverage {
+            val,
+            err,
+            converged: err.is_finite() && err <= epsrel * val.abs(),
+        }
+    }
+}
+
+/// Above this value of `x = mass/temperature`, quantum corrections are
+/// exponentially small and the Maxwell-Boltzmann average is used.
+const QUANTUM_STATISTICS_MAX_X: f64 = 30.0;
+
+/// Above this value of `x`, the Maxwell-Boltzmann average is written in terms
+/// of the relative velocity, with the Bessel functions replaced by their
+/// asymptotic series.
+const NON_RELATIVISTIC_MIN_X: f64 = 100.0;
+
+/// Breakpoints beyond this value of `t = x (z - 2)` are dropped, since the
+/// kernel is suppressed by `exp(-t)`.
+const MAX_RESCALED_ENERGY: f64 = 750.0;
 
 impl KineticMixing {
     /// Compute the thermalized annihilation cross section for
     /// chi + chibar -> anything for a given `x=mass/temperature`.
     pub fn thermal_cross_section(&self, x: f64) -> f64 {
+        self.thermal_cross_section_with_error(x).val
+    }
+    /// Compute the thermalized annihilation cross section, failing if `x` is
+    /// not positive or the integration returns a non-finite value or error.
+    pub fn try_thermal_cross_section(&self, x: f64) -> Result<f64, ModelError> {
+        check_parameter("x", x, "x > 0", |x| x > 0.0)?;
+        let avg = self.thermal_cross_section_with_error(x);
+        if avg.val.is_finite() && avg.err.is_finite() {
+            Ok(avg.val)
+        } else {
+            Err(ModelError::ThermalAverageFailed {
+                x,
+                val: avg.val,
+                err: avg.err,
+            })
+        }
+    }
+    /// Compute the thermalized annihilation cross section for a given
+    /// `x=mass/temperature` together with the integration error estimate.
+    /// If `bound_states` is set, the effective cross section from bound-state
+    /// formation is included, and its error estimate is added to that of the
+    /// annihilation average.
+    pub fn thermal_cross_section_with_error(&self, x: f64) -> ThermalAverage {
+        let start = Instant::now();
+        let mut avg = self.thermal_annihilation_average(x);
+        if self.bound_states {
+            let (val, err) = self.bound_state_cross_section_with_error(x);
+            avg = ThermalAverage::new(avg.val + val, avg.err + err, self.epsrel);
+        }
+        self.counters
+            .record_thermal_average(x, &avg, start.elapsed());
+        avg
+    }
+    fn thermal_annihilation_average(&self, x: f64) -> ThermalAverage {
+        if self.statistics != Statistics::MaxwellBoltzmann && x < QUANTUM_STATISTICS_MAX_X {
+            return self.thermal_cross_section_quantum(x);
+        }
+        self.thermal_average_maxwell_boltzmann(x, |cme| {
+            self.enhanced_annihilation_cross_section(cme)
+        })
+    }
+    /// Compute the thermalized annihilation cross section into the final
+    /// state `fs` for a given `x=mass/temperature`, including the Sommerfeld
+    /// factor. Maxwell-Boltzmann statistics are used for chi.
+    pub fn thermal_partial_cross_section(&self, x: f64, fs: KineticMixingFinalStates) -> f64 {
+        self.thermal_average_maxwell_boltzmann(x, |cme| {
+            self.partial_annihilation_cross_section(cme, fs) * self.sommerfeld_factor_at_cme(cme)
+        })
+        .val
+    }
+    /// Compute the Maxwell-Boltzmann average of the cross section `sigma(cme)`.
+    ///
+    /// The integral is done in `t = x (z - 2)`, with `z = sqrt(s) / mx`, so
+    /// that the range contributing at large `x`, `z - 2 ~ 1 / x`, is mapped
+    /// onto `t ~ 1`. Above `NON_RELATIVISTIC_MIN_X` the kernel is written as
+    /// the average of `sigma v` over the non-relativistic distribution of the
+    /// relative velocity, times a correction factor for the relativistic
+    /// kinematics in which the Bessel functions are replaced by their
+    /// asymptotic series. Both forms are exact and agree at the switch.
+    fn thermal_average_maxwell_boltzmann<F>(&self, x: f64, sigma: F) -> ThermalAverage
+    where
+        F: Fn(f64) -> f64,
+    {
         let m = self.mx;
-        let denom = 2.0 * x.cyl_bessel_kn_scaled(2);
-        let pf = x / (denom * denom);
-        let integrand = |z: f64| -> f64 {
-            let z2 = z * z;
-            let sig = self.annihilation_cross_section(m * z);
-            let kernal = z2 * (z2 - 4.0) * (x * z).cyl_bessel_k1_scaled() * (-x * (z - 2.0)).exp();
-            sig * kernal
+        let non_relativistic = x >= NON_RELATIVISTIC_MIN_X;
+        // Normalization of the kernel, 1 / (2 K2(x))^2 or its asymptotic form.
+        let norm = if non_relativistic {
+            2.0 / PI.sqrt() / bessel_k_asymptotic_factor(2, x).powi(2)
+        } else {
+            0.25 / x.cyl_bessel_kn_scaled(2).powi(2)
+        };
+        let kernal = |t: f64| -> f64 {
+            let y = t / x;
+            let z = 2.0 + y;
+            if non_relativistic {
+                // 2 / sqrt(pi) sqrt(t) exp(-t) v dt, with v the relative velocity.
+                let v = 2.0 * (y * (4.0 + y)).sqrt() / z;
+                let correction = (0.5 * z).powf(2.5)
+                    * (1.0 + 0.25 * y).sqrt()
+                    * bessel_k_asymptotic_factor(1, x * z);
+                norm * t.sqrt() * (-t).exp() * v * correction
+            } else {
+                // x / (2 K2(x))^2 z^2 (z^2 - 4) K1(x z) dz
+                norm * z * z * y * (4.0 + y) * (x * z).cyl_bessel_k1_scaled() * (-t).exp()
+            }
         };
+        let (val, err) =
+            self.integrate_rescaled_energy(x, |t| sigma(m * (2.0 + t / x)) * kernal(t));
+        ThermalAverage::new(val, err, self.epsrel)
+    }
+    /// Integrate `integrand(t)` over `t = x (z - 2)` from zero to infinity,
+    /// returning the value and error estimate. `integrand` must be linear in
+    /// the cross sections it contains.
+    ///
+    /// A narrow `V` resonance is integrated analytically within
+    /// `narrow_resonance_window`: there the cross sections are
+    /// `N / ((s - s0)^2 + c)`, with `s0` and `c = D(s0)` from
+    /// `propagator_peak` and `N` and the rest of the integrand evaluated at the
+    /// peak. The arctangent below is therefore exact for the propagator of the
+    /// `Fixed` and `PoleMass` schemes and holds to leading order in
+    /// `widthv / mv` for `Running`. Using `ds = 2 m sqrt(s0) dz` and
+    /// `dt = x dz`, the window contributes
+    /// `x integrand(t_r) sqrt(c) / (2 m sqrt(s0)) [atan((s - s0) / sqrt(c))]_lo^hi`.
+    fn integrate_rescaled_energy<F>(&self, x: f64, integrand: F) -> (f64, f64)
+    where
+        F: Fn(f64) -> f64,
+    {
+        let m = self.mx;
+        let window = self
+            .narrow_resonance_window()
+            .map(|(lo, hi)| (x * (lo - 2.0), x * (hi - 2.0)));
+        let f = |t: f64| -> f64 {
+            self.counters.count_integrand();
+            match window {
+                Some((lo, hi)) if t > lo && t < hi => 0.0,
+                _ => integrand(t),
+            }
+        };
+
+        let result = self
+            .thermal_average_integrator(x)
+            .integrate(f, 0.0, f64::INFINITY);
+        let mut val = result.val;
+        if let Some((lo, hi)) = window {
+            let (s0, c) = self.propagator_peak();
+            let (mr, mw) = (s0.sqrt(), c.sqrt());
+            let tr = x * (mr / m - 2.0);
+            let atan = |t: f64| {
+                let z = 2.0 + t / x;
+                ((m * m * z * z - s0) / mw).atan()
+            };
+            self.counters.count_integrand();
+            val += x * integrand(tr) * mw / (2.0 * m * mr) * (atan(hi) - atan(lo));
+        }
+        (val, result.err)
+    }
+    /// Build the integrator for thermal averages in `t = x (z - 2)`, with the
+    /// breakpoints computed in `z` by `update_derived` rescaled to `x`.
+    fn thermal_average_integrator(&self, x: f64) -> GaussKronrodIntegrator {
+        let singular_points: Vec<f64> = self
+            .singular_points
+            .iter()
+            .map(|z| x * (z - 2.0))
+            .filter(|&t| t < MAX_RESCALED_ENERGY)
+            .collect();
+        GaussKronrodIntegratorBuilder::default()
+            .epsabs(0.0)
+            .epsrel(self.epsrel)
+            .singular_points(singular_points)
+            .limit(1000)
+            .build()
+    }
+    /// Compute the window in `z = sqrt(s) / mx` around the `V` resonance that
+    /// is integrated analytically, if the resonance is narrow enough. A
+    /// stable `V`, with `widthv = 0`, has no window: the cross sections
+    /// through `V` then vanish, or diverge at the pole for a width set to zero
+    /// by hand, and there is nothing to integrate analytically.
+    fn narrow_resonance_window(&self) -> Option<(f64, f64)> {
+        let zr = self.mv / self.mx;
+        let dz = NARROW_WIDTH_WINDOW * self.widthv / self.mx;
+        if self.widthv > 0.0 && self.widthv < NARROW_WIDTH_RATIO * self.mv && zr - dz > 2.0 {
+            Some((zr - dz, zr + dz))
+        } else {
+            None
+        }
+    }
+    /// Compute the thermalized annihilation cross section using quantum
+    /// statistics for chi, including Pauli blocking of fermionic and Bose
+    /// enhancement of bosonic final states.
+    ///
+    /// Writing `E+ = E1 + E2` and `E- = E1 - E2`, the `E-` integral of the
+    /// phase-space densities is done analytically. The final-state factors
+    /// are evaluated for final-state particles carrying `E+ / 2`. Only the
+    /// `V V`, `W W` and `V Z` final states are treated as bosonic; `h Z` opens
+    /// far above the temperatures where the final-state factors matter. As
+    /// for Maxwell-Boltzmann statistics, the outer integral is done in
+    /// `t = x (z - 2)` with the same breakpoints and narrow-resonance window.
+    fn thermal_cross_section_quantum(&self, x: f64) -> ThermalAverage {
+        let m = self.mx;
+        let temp = m / x;
+        let eta = self.statistics.eta();
+        let inner_gk = GaussKronrodIntegratorBuilder::default()
+            .epsabs(0.0)
+            .epsrel(self.epsrel)
+            .limit(200)
+            .build();
+
+        // Integral over E+ / T = x z + t of the E- integrated phase-space
+        // density, up to the factor 8 T^2 exp(-x z). `blocking` is +1 for
+        // fermionic and -1 for bosonic final states.
+        let phase_space = |z: f64, blocking: f64| -> f64 {
+            let beta = (1.0 - 4.0 / (z * z)).sqrt();
+            let integrand = |t: f64| -> f64 {
+                let e = x * z + t;
+                let u = 0.5 * beta * (e * e - x * x * z * z).sqrt();
+                let c = if eta > 0.0 {
+                    (0.25 * e).tanh()
+                } else {
+                    1.0 / (0.25 * e).tanh()
+                };
+                let fs = 1.0 / (1.0 + blocking * (-0.5 * e).exp());
+                (-t).exp() / (-(-e).exp_m1()) * (c * (0.5 * u).tanh()).atanh() * fs * fs
+            };
+            inner_gk.integrate(integrand, 0.0, f64::INFINITY).val
+        };
+
+        let integrand = |t: f64| -> f64 {
+            let z = 2.0 + t / x;
+            let sfac = self.sommerfeld_factor_at_cme(m * z);
+            let sig_b = (self.sigma_xx_to_vv(m * z)
+                + self.sigma_xx_to_ww(m * z)
+                + self.sigma_xx_to_vz(m * z))
+                * sfac;
+            let sig_f = self.annihilation_cross_section(m * z) * sfac - sig_b;
+            // dz = dt / x
+            let kernal = z * z * (z * z - 4.0).sqrt() * (-t).exp() / x;
+            kernal * (sig_f * phase_space(z, 1.0) + sig_b * phase_space(z, -1.0))
+        };
+
+        // n^2 <sigma v> = g^2 m^4 T^2 / (4 pi^4) int dz z^2 sqrt(z^2 - 4) sigma exp(-x z) J(z)
+        let g = 2.0;
+        let neq_scaled = self.neq(temp) * x.exp();
+        let pf = g * g * m.powi(4) * temp * temp / (4.0 * PI.powi(4) * neq_scaled * neq_scaled);
+        let (val, err) = self.integrate_rescaled_energy(x, integrand);
+        ThermalAverage::new(pf * val, pf * err, self.epsrel)
+    }
+}
 
-        pf * self.gk.integrate(integrand, 2.0, f64::INFINITY).val
+/// Compute `sqrt(2 y / pi) exp(y) K_nu(y)` from its asymptotic series, which
+/// is accurate to double precision for `y >= NON_RELATIVISTIC_MIN_X`.
+fn bessel_k_asymptotic_factor(nu: i32, y: f64) -> f64 {
+    let mu = f64::from(4 * nu * nu);
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for k in 1..=30 {
+        let j = f64::from(2 * k - 1);
+        term *= (mu - j * j) / (8.0 * f64::from(k) * y);
+        sum += term;
+        if term.abs() < f64::EPSILON * sum.abs() {
+            break;
+        }
     }
+    sum
 }
@@ -0,0 +1,229 @@
+use super::propagator::WidthScheme;
+use super::{KineticMixing, KineticMixingFinalStates, ANNIHILATION_CHANNELS};
+use crate::sm_parameters::Quark;
+use std::f64::consts::PI;
+
+/// Expansions with a radius of convergence in `v^2` below this value are
+/// flagged, since they cannot be trusted at freeze-out velocities,
+/// `v^2 ~ 6 / x_f`.
+const EXPANSION_MIN_RADIUS: f64 = 0.3;
+
+/// Reasons for which the velocity expansion may not describe the cross section.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExpansionWarning {
+    /// A final state with the given total mass opens close to `sqrt(s) = 2 mx`.
+    Threshold(f64),
+    /// The `V` resonance lies close to `sqrt(s) = 2 mx`.
+    Resonance,
+    /// The running width contains `V -> chi chibar`, which opens at
+    /// `sqrt(s) = 2 mx` and introduces odd powers of `v`.
+    RunningWidth,
+    /// The electromagnetic coupling, the quark masses and the QCD corrections
+    /// run with `sqrt(s)`, while the analytic expansions of the s-channel
+    /// channels into fermions hold them fixed at `2 mx`.
+    RunningCouplings,
+}
+
+/// Coefficients of `sigma v = a + b v^2 + ...`, with `v` the relative velocity
+/// of chi and chibar and `s = 4 mx^2 / (1 - v^2 / 4)`.
+#[derive(Clone, Debug)]
+pub struct VelocityExpansion {
+    /// s-wave coefficient in GeV^-2.
+    pub a: f64,
+    /// p-wave coefficient in GeV^-2.
+    pub b: f64,
+    /// Estimated radius of convergence of the expansion in `v^2`, set by the
+    /// nearest threshold or by the `V` pole.
+    pub radius: f64,
+    /// Reasons for which the expansion may be unreliable.
+    pub warnings: Vec<ExpansionWarning>,
+}
+
+impl KineticMixing {
+    /// Compute the s-wave and p-wave coefficients of `sigma v` for
+    /// chi + chibar -> `fs`.
+    ///
+    /// The s-channel channels into fermions and the s-wave of `V V` are
+    /// expanded analytically; the p-wave of `V V` and the remaining channels
+    /// are obtained by fitting `sigma v` at small velocities.
+    pub fn velocity_expansion(&self, fs: KineticMixingFinalStates) -> VelocityExpansion {
+        if fs == KineticMixingFinalStates::All {
+            let mut total = VelocityExpansion {
+                a: 0.0,
+                b: 0.0,
+                radius: 4.0,
+                warnings: vec![],
+            };
+            for channel in ANNIHILATION_CHANNELS.iter() {
+                let exp = self.velocity_expansion(*channel);
+                total.a += exp.a;
+                total.b += exp.b;
+                total.radius = total.radius.min(exp.radius);
+                for warning in exp.warnings {
+                    if !total.warnings.contains(&warning) {
+                        total.warnings.push(warning);
+                    }
+                }
+            }
+            return total;
+        }
+
+        let thresholds = match fs {
+            KineticMixingFinalStates::UU => vec![2.0 * self.sm.up_quark_mass],
+            KineticMixingFinalStates::CC => vec![2.0 * self.sm.charm_quark_mass],
+            KineticMixingFinalStates::TT => vec![2.0 * self.sm.top_quark_mass],
+            KineticMixingFinalStates::DD => vec![2.0 * self.sm.down_quark_mass],
+            KineticMixingFinalStates::SS => vec![2.0 * self.sm.strange_quark_mass],
+            KineticMixingFinalStates::BB => vec![2.0 * self.sm.bottom_quark_mass],
+            KineticMixingFinalStates::EE => vec![2.0 * self.sm.electron_mass],
+            KineticMixingFinalStates::MuMu => vec![2.0 * self.sm.muon_mass],
+            KineticMixingFinalStates::TauTau => vec![2.0 * self.sm.tau_mass],
+            KineticMixingFinalStates::HiggsZ => vec![self.sm.higgs_mass + self.sm.z_boson_mass],
+            KineticMixingFinalStates::VV => vec![2.0 * self.mv],
+            // The on-shell part of the three-body channel is subtracted above 2 mv.
+            KineticMixingFinalStates::VFF => vec![self.mv, 2.0 * self.mv],
+            KineticMixingFinalStates::WW => vec![2.0 * self.sm.w_boson_mass],
+            KineticMixingFinalStates::VZ => vec![self.mv + self.sm.z_boson_mass],
+            _ => vec![],
+        };
+        let s_channel = !matches!(
+            fs,
+            KineticMixingFinalStates::XX
+                | KineticMixingFinalStates::VV
+                | KineticMixingFinalStates::VFF
+                | KineticMixingFinalStates::VZ
+        );
+
+        // Singularities of sigma v in s, mapped onto v^2 through s - 4 mx^2 ~ mx^2 v^2.
+        let m2 = self.mx * self.mx;
+        let s0 = 4.0 * m2;
+        let mut warnings = vec![];
+        let mut radius: f64 = 4.0;
+        for &threshold in thresholds.iter() {
+            let r = (s0 - threshold * threshold).abs() / m2;
+            if r < EXPANSION_MIN_RADIUS {
+                warnings.push(ExpansionWarning::Threshold(threshold));
+            }
+            radius = radius.min(r);
+        }
+        if s_channel {
+            let r = self.propagator_denominator(s0).sqrt() / m2;
+            if r < EXPANSION_MIN_RADIUS {
+                warnings.push(ExpansionWarning::Resonance);
+            }
+            if self.width_scheme == WidthScheme::Running {
+                warnings.push(ExpansionWarning::RunningWidth);
+            }
+            if self.sm.running {
+                warnings.push(ExpansionWarning::RunningCouplings);
+            }
+            radius = radius.min(r);
+        }
+
+        // Running couplings and quark masses are evaluated at threshold.
+        let q = 2.0 * self.mx;
+        let pf = self.sm.alpha_em_at(q) * self.eps.powi(2) * self.gvxx.powi(2)
+            / self.sm.cos_theta_weak().powi(2);
+        let pf_quark = pf * self.sm.qcd_correction(q);
+        let mq = |quark: Quark| self.sm.quark_mass_at(quark, q);
+        let (a, b) = match fs {
+            KineticMixingFinalStates::UU => {
+                self.s_channel_expansion(pf_quark / 36.0, 17.0, 7.0, mq(Quark::Up))
+            }
+            KineticMixingFinalStates::CC => {
+                self.s_channel_expansion(pf_quark / 36.0, 17.0, 7.0, mq(Quark::Charm))
+            }
+            KineticMixingFinalStates::TT => {
+                self.s_channel_expansion(pf_quark / 36.0, 17.0, 7.0, mq(Quark::Top))
+            }
+            KineticMixingFinalStates::DD => {
+                self.s_channel_expansion(pf_quark / 36.0, 5.0, -17.0, mq(Quark::Down))
+            }
+            KineticMixingFinalStates::SS => {
+                self.s_channel_expansion(pf_quark / 36.0, 5.0, -17.0, mq(Quark::Strange))
+            }
+            KineticMixingFinalStates::BB => {
+                self.s_channel_expansion(pf_quark / 36.0, 5.0, -17.0, mq(Quark::Bottom))
+            }
+            KineticMixingFinalStates::EE => {
+                self.s_channel_expansion(pf / 12.0, 5.0, 7.0, self.sm.electron_mass)
+            }
+            KineticMixingFinalStates::MuMu => {
+                self.s_channel_expansion(pf / 12.0, 5.0, 7.0, self.sm.muon_mass)
+            }
+            KineticMixingFinalStates::TauTau => {
+                self.s_channel_expansion(pf / 12.0, 5.0, 7.0, self.sm.tau_mass)
+            }
+            KineticMixingFinalStates::NueNue
+            | KineticMixingFinalStates::NumuNumu
+            | KineticMixingFinalStates::NutauNutau => {
+                self.s_channel_expansion(pf / 12.0, 1.0, 0.0, 0.0)
+            }
+            KineticMixingFinalStates::HiggsZ => {
+                self.numerical_velocity_expansion(|cme| self.sigma_xx_to_hz(cme), radius)
+            }
+            KineticMixingFinalStates::VV if self.mv < self.mx => {
+                // s-wave of the t- and u-channel exchange of chi.
+                let r = self.mv * self.mv / m2;
+                let a = self.gvxx.powi(4) / (16.0 * PI * m2) * (1.0 - r).powf(1.5)
+                    / (1.0 - 0.5 * r).powi(2);
+                let (_, b) =
+                    self.numerical_velocity_expansion(|cme| self.sigma_xx_to_vv(cme), radius);
+                (a, b)
+            }
+            KineticMixingFinalStates::VFF => {
+                self.numerical_velocity_expansion(|cme| self.sigma_xx_to_vff(cme), radius)
+            }
+            KineticMixingFinalStates::WW => {
+                self.numerical_velocity_expansion(|cme| self.sigma_xx_to_ww(cme), radius)
+            }
+            KineticMixingFinalStates::VZ => {
+                self.numerical_velocity_expansion(|cme| self.sigma_xx_to_vz(cme), radius)
+            }
+            _ => (0.0, 0.0),
+        };
+
+        VelocityExpansion {
+            a,
+            b,
+            radius,
+            warnings,
+        }
+    }
+    /// Expand `sigma v = k (s + 2 mx^2) sqrt(s - 4 mf^2) (c1 s + c2 mf^2) / (s^(3/2) D(s))`,
+    /// the common form of the s-channel annihilations into fermions, with
+    /// `D(s)` the propagator denominator. With `ds / dv^2 = mx^2` at `v = 0`,
+    /// `b = mx^2 d(sigma v)/ds`.
+    fn s_channel_expansion(&self, k: f64, c1: f64, c2: f64, mf: f64) -> (f64, f64) {
+        let m2 = self.mx * self.mx;
+        let s = 4.0 * m2;
+        let mf2 = mf * mf;
+        if s <= 4.0 * mf2 {
+            return (0.0, 0.0);
+        }
+        let den = self.propagator_denominator(s);
+        let a =
+            k * (s + 2.0 * m2) * (s - 4.0 * mf2).sqrt() * (c1 * s + c2 * mf2) / (s.powf(1.5) * den);
+        let dlog = 1.0 / (s + 2.0 * m2) + 0.5 / (s - 4.0 * mf2) + c1 / (c1 * s + c2 * mf2)
+            - 1.5 / s
+            - self.propagator_denominator_derivative(s) / den;
+        (a, m2 * a * dlog)
+    }
+    /// Fit `a + b v^2 + c v^4` to `sigma v` at three small velocities, chosen
+    /// well inside the radius of convergence `radius`.
+    fn numerical_velocity_expansion<F>(&self, sigma: F, radius: f64) -> (f64, f64)
+    where
+        F: Fn(f64) -> f64,
+    {
+        let h = 1e-4 * radius.clamp(1e-4, 1.0);
+        let sigmav = |v2: f64| -> f64 {
+            let cme = 2.0 * self.mx / (1.0 - 0.25 * v2).sqrt();
+            sigma(cme) * v2.sqrt()
+        };
+        let (f1, f2, f3) = (sigmav(h), sigmav(2.0 * h), sigmav(3.0 * h));
+        (
+            3.0 * f1 - 3.0 * f2 + f3,
+            (-5.0 * f1 + 8.0 * f2 - 3.0 * f3) / (2.0 * h),
+        )
+    }
+}
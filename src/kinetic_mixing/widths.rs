@@ -1,125 +1,216 @@
 use super::{KineticMixing, KineticMixingFinalStates};
-use haliax_constants::prelude::*;
+use crate::sm_parameters::Quark;
 
 impl KineticMixing {
-    /// Compute the partial width for V -> down-type quarks.
-    pub fn width_v_to_ququ(&self, mf: f64) -> f64 {
-        if self.mv > 2.0 * mf {
-            return (ALPHA_EM
+    /// Compute the partial width for V -> up-type quarks.
+    ///
+    /// `V` couples to fermions through their hypercharge, with vector and
+    /// axial couplings `e eps / cos(theta_W) (Y_L +- Y_R) / 2`, so that
+    /// `Gamma = N_c alpha eps^2 / (3 cos^2(theta_W)) beta mv (g_v^2 (1 + 2 r) + g_a^2 (1 - 4 r))`
+    /// with `r = mf^2 / mv^2`. For up-type quarks, `Y_L = 1/6` and
+    /// `Y_R = 2/3`, this is proportional to `(17 mv^2 + 7 mq^2) / 72`, for
+    /// down-type quarks, `Y_R = -1/3`, to `(5 mv^2 - 17 mq^2) / 72` and for
+    /// charged leptons to `(5 mv^2 + 7 ml^2) / 8`. The same combinations
+    /// appear in the cross sections and their velocity expansions.
+    pub fn width_v_to_ququ(&self, quark: Quark) -> f64 {
+        self.width_v_to_ququ_at(self.mv, quark)
+    }
+    pub(super) fn width_v_to_ququ_at(&self, mv: f64, quark: Quark) -> f64 {
+        if mv > 2.0 * self.sm.quark_mass(quark) {
+            let mq = self.sm.quark_mass_at(quark, mv);
+            (self.sm.alpha_em_at(mv)
+                * self.sm.qcd_correction(mv)
                 * self.eps.powi(2)
-                * (-4.0 * mf.powi(2) + self.mv.powi(2)).sqrt()
-                * (7.0 * mf.powi(2) + 17.0 * self.mv.powi(2)))
-                / (24.0 * COS_THETA_WEAK.powi(2) * self.mv.powi(2));
+                * (-4.0 * mq.powi(2) + mv.powi(2)).sqrt()
+                * (7.0 * mq.powi(2) + 17.0 * mv.powi(2)))
+                / (72.0 * self.sm.cos_theta_weak().powi(2) * mv.powi(2))
         } else {
             0.0
         }
     }
-    /// Compute the partial width for V -> leptons.
-    pub fn width_v_to_qdqd(&self, mf: f64) -> f64 {
-        if self.mv > 2.0 * mf {
-            return (ALPHA_EM
+    /// Compute the partial width for V -> down-type quarks, see
+    /// `width_v_to_ququ`.
+    pub fn width_v_to_qdqd(&self, quark: Quark) -> f64 {
+        self.width_v_to_qdqd_at(self.mv, quark)
+    }
+    pub(super) fn width_v_to_qdqd_at(&self, mv: f64, quark: Quark) -> f64 {
+        if mv > 2.0 * self.sm.quark_mass(quark) {
+            let mq = self.sm.quark_mass_at(quark, mv);
+            (self.sm.alpha_em_at(mv)
+                * self.sm.qcd_correction(mv)
                 * self.eps.powi(2)
-                * (-4.0 * mf.powi(2) + self.mv.powi(2)).sqrt()
-                * (7.0 * mf.powi(2) + 5.0 * self.mv.powi(2)))
-                / (8.0 * COS_THETA_WEAK.powi(2) * self.mv.powi(2));
+                * (-4.0 * mq.powi(2) + mv.powi(2)).sqrt()
+                * (5.0 * mv.powi(2) - 17.0 * mq.powi(2)))
+                / (72.0 * self.sm.cos_theta_weak().powi(2) * mv.powi(2))
         } else {
             0.0
         }
     }
     /// Compute the partial width for V -> leptons.
     pub fn width_v_to_ll(&self, mf: f64) -> f64 {
-        if self.mv > 2.0 * mf {
-            return (ALPHA_EM
+        self.width_v_to_ll_at(self.mv, mf)
+    }
+    pub(super) fn width_v_to_ll_at(&self, mv: f64, mf: f64) -> f64 {
+        if mv > 2.0 * mf {
+            (self.sm.alpha_em_at(mv)
                 * self.eps.powi(2)
-                * (-4.0 * mf.powi(2) + self.mv.powi(2)).sqrt()
-                * (7.0 * mf.powi(2) + 5.0 * self.mv.powi(2)))
-                / (24.0 * COS_THETA_WEAK.powi(2) * self.mv.powi(2));
+                * (-4.0 * mf.powi(2) + mv.powi(2)).sqrt()
+                * (7.0 * mf.powi(2) + 5.0 * mv.powi(2)))
+                / (24.0 * self.sm.cos_theta_weak().powi(2) * mv.powi(2))
         } else {
             0.0
         }
     }
     /// Compute the partial width for V -> neutrinos.
     pub fn width_v_to_nunu(&self) -> f64 {
-        ALPHA_EM * self.eps.powi(2) * self.mv / (24.0 * COS_THETA_WEAK.powi(2))
+        self.width_v_to_nunu_at(self.mv)
+    }
+    pub(super) fn width_v_to_nunu_at(&self, mv: f64) -> f64 {
+        self.sm.alpha_em_at(mv) * self.eps.powi(2) * mv / (24.0 * self.sm.cos_theta_weak().powi(2))
     }
     /// Compute the partial width for V -> higgs + z-boson.
     pub fn width_v_to_hz(&self) -> f64 {
-        if self.mv > HIGGS_MASS + Z_BOSON_MASS {
-            (ALPHA_EM.powi(2)
+        self.width_v_to_hz_at(self.mv)
+    }
+    pub(super) fn width_v_to_hz_at(&self, mv: f64) -> f64 {
+        if mv > self.sm.higgs_mass + self.sm.z_boson_mass {
+            (self.sm.alpha_em_at(mv).powi(2)
                 * self.eps.powi(2)
-                * (-(HIGGS_MASS.powi(2))
-                    + (HIGGS_MASS.powi(2) + self.mv.powi(2) - W_BOSON_MASS.powi(2)).powi(2)
-                        / (4.0 * self.mv.powi(2)))
+                * (-(self.sm.higgs_mass.powi(2))
+                    + (self.sm.higgs_mass.powi(2) + mv.powi(2) - self.sm.w_boson_mass.powi(2))
+                        .powi(2)
+                        / (4.0 * mv.powi(2)))
                 .sqrt()
-                * (2.0 * self.mv.powi(2) * W_BOSON_MASS.powi(2)
-                    + (-(HIGGS_MASS.powi(2)) + self.mv.powi(2) + W_BOSON_MASS.powi(2)).powi(2)
+                * (2.0 * mv.powi(2) * self.sm.w_boson_mass.powi(2)
+                    + (-(self.sm.higgs_mass.powi(2)) + mv.powi(2) + self.sm.w_boson_mass.powi(2))
+                        .powi(2)
                         / 4.)
                 * std::f64::consts::PI
-                * HIGGS_VEV.powi(2))
+                * self.sm.higgs_vev.powi(2))
                 / (6.0
-                    * COS_THETA_WEAK.powi(4)
-                    * self.mv.powi(4)
-                    * W_BOSON_MASS.powi(2)
-                    * SIN_THETA_WEAK.powi(2))
+                    * self.sm.cos_theta_weak().powi(4)
+                    * mv.powi(4)
+                    * self.sm.w_boson_mass.powi(2)
+                    * self.sm.sin_theta_weak().powi(2))
+        } else {
+            0.0
+        }
+    }
+    /// Compute the mixing angle between `V` and the Z-boson induced by the
+    /// kinetic mixing.
+    ///
+    /// After removing the kinetic mixing, the mass matrix of the Z-boson and
+    /// `V` is `[[mz^2, d mz^2], [d mz^2, mv^2 + d^2 mz^2]]` with
+    /// `d = eps tan(theta_W)`, which is diagonalised by the angle
+    /// `tan(2 theta) = 2 d mz^2 / (mz^2 - mv^2 - d^2 mz^2)`. Away from
+    /// `mv = mz` this is `d mz^2 / (mz^2 - mv^2)`; close to it the states mix
+    /// maximally and the angle stays within `[-pi / 4, pi / 4]`.
+    pub fn vz_mixing_angle(&self) -> f64 {
+        self.vz_mixing_angle_at(self.mv)
+    }
+    pub(super) fn vz_mixing_angle_at(&self, mv: f64) -> f64 {
+        let mz2 = self.sm.z_boson_mass.powi(2);
+        let d = self.eps * self.sm.sin_theta_weak() / self.sm.cos_theta_weak();
+        if d == 0.0 {
+            return 0.0;
+        }
+        // At the degeneracy the ratio is infinite and the angle is pi / 4.
+        0.5 * (2.0 * d * mz2 / (mz2 - mv.powi(2) - d * d * mz2)).atan()
+    }
+    /// Compute the partial width for V -> W+ + W-, through the Z-boson
+    /// component of `V`.
+    pub fn width_v_to_ww(&self) -> f64 {
+        self.width_v_to_ww_at(self.mv)
+    }
+    pub(super) fn width_v_to_ww_at(&self, mv: f64) -> f64 {
+        if mv > 2.0 * self.sm.w_boson_mass {
+            // g_VWW = g cos(theta_W) theta_vz, or e eps mz^2 / (mz^2 - mv^2)
+            // away from mv = mz.
+            let gvww2 = 4.0
+                * std::f64::consts::PI
+                * self.sm.alpha_em_at(mv)
+                * self.sm.cos_theta_weak().powi(2)
+                / self.sm.sin_theta_weak_sqrd
+                * self.vz_mixing_angle_at(mv).powi(2);
+            let r = (self.sm.w_boson_mass / mv).powi(2);
+            gvww2 * mv / (192.0 * std::f64::consts::PI)
+                * r.powi(-2)
+                * (1.0 - 4.0 * r).powf(1.5)
+                * (1.0 + 20.0 * r + 12.0 * r * r)
         } else {
             0.0
         }
     }
     /// Compute the total width or parital of the vector meidator.
     pub fn width_v_to_xx(&self) -> f64 {
-        if self.mv > 2.0 * self.mx {
+        self.width_v_to_xx_at(self.mv)
+    }
+    pub(super) fn width_v_to_xx_at(&self, mv: f64) -> f64 {
+        if mv > 2.0 * self.mx {
             (self.gvxx.powi(2)
-                * (-4.0 * self.mx.powi(2) + self.mv.powi(2)).sqrt()
-                * (2.0 * self.mx.powi(2) + self.mv.powi(2)))
-                / (12.0 * self.mv.powi(2) * std::f64::consts::PI)
+                * (-4.0 * self.mx.powi(2) + mv.powi(2)).sqrt()
+                * (2.0 * self.mx.powi(2) + mv.powi(2)))
+                / (12.0 * mv.powi(2) * std::f64::consts::PI)
         } else {
             0.0
         }
     }
     pub fn vm_decay_width(&self) -> f64 {
-        self.width_v_to_ququ(UP_QUARK_MASS)
-            + self.width_v_to_ququ(CHARM_QUARK_MASS)
-            + self.width_v_to_ququ(TOP_QUARK_MASS)
-            + self.width_v_to_qdqd(DOWN_QUARK_MASS)
-            + self.width_v_to_qdqd(STRANGE_QUARK_MASS)
-            + self.width_v_to_qdqd(BOTTOM_QUARK_MASS)
-            + self.width_v_to_ll(ELECTRON_MASS)
-            + self.width_v_to_ll(MUON_MASS)
-            + self.width_v_to_ll(TAU_MASS)
-            + 3.0 * self.width_v_to_nunu()
-            + self.width_v_to_hz()
-            + self.width_v_to_xx()
+        self.vm_decay_width_at(self.mv)
+    }
+    /// Compute the total width the vector mediator would have if its mass
+    /// were `mv`, keeping all couplings fixed.
+    pub fn vm_decay_width_at(&self, mv: f64) -> f64 {
+        self.width_v_to_ququ_at(mv, Quark::Up)
+            + self.width_v_to_ququ_at(mv, Quark::Charm)
+            + self.width_v_to_ququ_at(mv, Quark::Top)
+            + self.width_v_to_qdqd_at(mv, Quark::Down)
+            + self.width_v_to_qdqd_at(mv, Quark::Strange)
+            + self.width_v_to_qdqd_at(mv, Quark::Bottom)
+            + self.width_v_to_ll_at(mv, self.sm.electron_mass)
+            + self.width_v_to_ll_at(mv, self.sm.muon_mass)
+            + self.width_v_to_ll_at(mv, self.sm.tau_mass)
+            + 3.0 * self.width_v_to_nunu_at(mv)
+            + self.width_v_to_hz_at(mv)
+            + self.width_v_to_ww_at(mv)
+            + self.width_v_to_xx_at(mv)
     }
     pub fn vm_partial_decay_width(&self, fs: KineticMixingFinalStates) -> f64 {
         match fs {
             KineticMixingFinalStates::All => {
-                self.width_v_to_ququ(UP_QUARK_MASS)
-                    + self.width_v_to_ququ(CHARM_QUARK_MASS)
-                    + self.width_v_to_ququ(TOP_QUARK_MASS)
-                    + self.width_v_to_qdqd(DOWN_QUARK_MASS)
-                    + self.width_v_to_qdqd(STRANGE_QUARK_MASS)
-                    + self.width_v_to_qdqd(BOTTOM_QUARK_MASS)
-                    + self.width_v_to_ll(ELECTRON_MASS)
-                    + self.width_v_to_ll(MUON_MASS)
-                    + self.width_v_to_ll(TAU_MASS)
+                self.width_v_to_ququ(Quark::Up)
+                    + self.width_v_to_ququ(Quark::Charm)
+                    + self.width_v_to_ququ(Quark::Top)
+                    + self.width_v_to_qdqd(Quark::Down)
+                    + self.width_v_to_qdqd(Quark::Strange)
+                    + self.width_v_to_qdqd(Quark::Bottom)
+                    + self.width_v_to_ll(self.sm.electron_mass)
+                    + self.width_v_to_ll(self.sm.muon_mass)
+                    + self.width_v_to_ll(self.sm.tau_mass)
                     + 3.0 * self.width_v_to_nunu()
                     + self.width_v_to_hz()
+                    + self.width_v_to_ww()
                     + self.width_v_to_xx()
             }
-            KineticMixingFinalStates::UU => self.width_v_to_ququ(UP_QUARK_MASS),
-            KineticMixingFinalStates::CC => self.width_v_to_ququ(CHARM_QUARK_MASS),
-            KineticMixingFinalStates::TT => self.width_v_to_ququ(TOP_QUARK_MASS),
-            KineticMixingFinalStates::DD => self.width_v_to_ququ(DOWN_QUARK_MASS),
-            KineticMixingFinalStates::SS => self.width_v_to_ququ(STRANGE_QUARK_MASS),
-            KineticMixingFinalStates::BB => self.width_v_to_ququ(BOTTOM_QUARK_MASS),
-            KineticMixingFinalStates::EE => self.width_v_to_ll(ELECTRON_MASS),
-            KineticMixingFinalStates::MuMu => self.width_v_to_ll(MUON_MASS),
-            KineticMixingFinalStates::TauTau => self.width_v_to_ll(TAU_MASS),
+            KineticMixingFinalStates::UU => self.width_v_to_ququ(Quark::Up),
+            KineticMixingFinalStates::CC => self.width_v_to_ququ(Quark::Charm),
+            KineticMixingFinalStates::TT => self.width_v_to_ququ(Quark::Top),
+            KineticMixingFinalStates::DD => self.width_v_to_qdqd(Quark::Down),
+            KineticMixingFinalStates::SS => self.width_v_to_qdqd(Quark::Strange),
+            KineticMixingFinalStates::BB => self.width_v_to_qdqd(Quark::Bottom),
+            KineticMixingFinalStates::EE => self.width_v_to_ll(self.sm.electron_mass),
+            KineticMixingFinalStates::MuMu => self.width_v_to_ll(self.sm.muon_mass),
+            KineticMixingFinalStates::TauTau => self.width_v_to_ll(self.sm.tau_mass),
             KineticMixingFinalStates::NueNue => self.width_v_to_nunu(),
             KineticMixingFinalStates::NumuNumu => self.width_v_to_nunu(),
             KineticMixingFinalStates::NutauNutau => self.width_v_to_nunu(),
             KineticMixingFinalStates::HiggsZ => self.width_v_to_hz(),
             KineticMixingFinalStates::XX => self.width_v_to_xx(),
+            KineticMixingFinalStates::WW => self.width_v_to_ww(),
+            // V -> V Z is kinematically forbidden.
+            KineticMixingFinalStates::VZ => 0.0,
+            // V cannot decay into final states containing itself.
+            KineticMixingFinalStates::VV | KineticMixingFinalStates::VFF => 0.0,
         }
     }
 }
@@ -1,3 +1,9 @@
 //pub mod boltzmann;
+pub mod cosmology;
+pub mod dof;
+pub mod error;
 pub mod kinetic_mixing;
+pub mod reaction_network;
+pub mod sm_parameters;
+pub mod statistics;
 //pub mod standard_dm_model;
@@ -0,0 +1,371 @@
+//! Coupled Boltzmann equations for an arbitrary set of dark-sector species.
+//!
+//! A network is declared by adding species and reactions between them. All
+//! Standard Model particles are assumed to be in thermal equilibrium, so only
+//! dark-sector species appear explicitly in a reaction. Inverse processes are
+//! included through detailed balance. The network is solved for `log(Y_i)` as
+//! a function of `log(x)`, with `x = mref / T`, using Radau5 with an analytic
+//! Jacobian.
+
+use crate::cosmology::{Cosmology, RadiationDomination};
+use crate::error::{check_parameter, check_solution, ModelError};
+use crate::statistics::{equilibrium_number_density, Statistics};
+use cyphus_diffeq::prelude::*;
+use cyphus_specfun::bessel::CylBesselK;
+use haliax_constants::prelude::*;
+use ndarray::prelude::*;
+use std::sync::Arc;
+
+/// A dark-sector species tracked by the network.
+#[derive(Clone, Debug)]
+pub struct Species {
+    /// Label used when reporting results.
+    pub name: String,
+    /// Mass of the species.
+    pub mass: f64,
+    /// Number of internal degrees of freedom.
+    pub g: f64,
+    /// Statistics used for the equilibrium abundance.
+    pub statistics: Statistics,
+}
+
+impl Species {
+    pub fn new(name: &str, mass: f64, g: f64, statistics: Statistics) -> Species {
+        Species {
+            name: name.to_string(),
+            mass,
+            g,
+            statistics,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Rate {
+    /// Thermally averaged `<sigma v^(n-1)>` as a function of temperature.
+    Scattering(Arc<dyn Fn(f64) -> f64 + Send + Sync>),
+    /// Rest-frame width of the decaying species.
+    Decay(f64),
+}
+
+/// A reaction between dark-sector species. Any Standard Model particles in
+/// the initial or final state are implicit.
+#[derive(Clone)]
+pub struct Reaction {
+    initial: Vec<usize>,
+    fin: Vec<usize>,
+    symmetry: f64,
+    rate: Rate,
+}
+
+impl Reaction {
+    /// Annihilation `a + b -> SM`. For `a == b` the symmetry factor is
+    /// included so that `dn_a/dt = -<sigma v> (n_a^2 - n_a,eq^2)`.
+    pub fn annihilation<F>(a: usize, b: usize, sigmav: F) -> Reaction
+    where
+        F: Fn(f64) -> f64 + Send + Sync + 'static,
+    {
+        Reaction {
+            initial: vec![a, b],
+            fin: vec![],
+            symmetry: if a == b { 0.5 } else { 1.0 },
+            rate: Rate::Scattering(Arc::new(sigmav)),
+        }
+    }
+    /// Conversion between dark-sector states, e.g. `a + a -> b + b` or
+    /// `a + SM -> b + SM`, with thermally averaged cross section `sigmav`.
+    /// For processes with a single dark particle in the initial state,
+    /// `sigmav` is the conversion rate `n_SM <sigma v>`.
+    pub fn conversion<F>(initial: Vec<usize>, fin: Vec<usize>, sigmav: F) -> Reaction
+    where
+        F: Fn(f64) -> f64 + Send + Sync + 'static,
+    {
+        Reaction {
+            initial,
+            fin,
+            symmetry: 1.0,
+            rate: Rate::Scattering(Arc::new(sigmav)),
+        }
+    }
+    /// Decay `parent -> daughters + SM` with rest-frame width `width`. The
+    /// thermally averaged time dilation `K1/K2` is applied and the inverse
+    /// decay follows from detailed balance.
+    pub fn decay(parent: usize, daughters: Vec<usize>, width: f64) -> Reaction {
+        Reaction {
+            initial: vec![parent],
+            fin: daughters,
+            symmetry: 1.0,
+            rate: Rate::Decay(width),
+        }
+    }
+    /// Number-changing process `a + a + a -> a + a` with thermally averaged
+    /// `<sigma v^2>`, normalized such that
+    /// `dn_a/dt = -<sigma v^2> (n_a^3 - n_a^2 n_a,eq)`.
+    pub fn three_to_two<F>(a: usize, sigmav2: F) -> Reaction
+    where
+        F: Fn(f64) -> f64 + Send + Sync + 'static,
+    {
+        Reaction {
+            initial: vec![a, a, a],
+            fin: vec![a, a],
+            symmetry: 1.0,
+            rate: Rate::Scattering(Arc::new(sigmav2)),
+        }
+    }
+    /// Number of times each species appears in the initial and final state.
+    fn multiplicities(&self, nspecies: usize) -> (Vec<f64>, Vec<f64>) {
+        let mut a = vec![0.0; nspecies];
+        let mut b = vec![0.0; nspecies];
+        for &i in self.initial.iter() {
+            a[i] += 1.0;
+        }
+        for &i in self.fin.iter() {
+            b[i] += 1.0;
+        }
+        (a, b)
+    }
+}
+
+/// A set of dark-sector species coupled through reactions.
+#[derive(Clone)]
+pub struct ReactionNetwork {
+    /// Reference mass used to define `x = mref / T`.
+    pub mref: f64,
+    /// Expansion history used when solving the network.
+    pub cosmology: Arc<dyn Cosmology + Send + Sync>,
+    /// Relative tolerance used when solving the network.
+    pub ode_reltol: f64,
+    /// Absolute tolerance used when solving the network.
+    pub ode_abstol: f64,
+    species: Vec<Species>,
+    reactions: Vec<Reaction>,
+}
+
+impl ReactionNetwork {
+    pub fn new(mref: f64) -> ReactionNetwork {
+        ReactionNetwork {
+            mref,
+            cosmology: Arc::new(RadiationDomination::default()),
+            ode_reltol: 1e-7,
+            ode_abstol: 1e-7,
+            species: vec![],
+            reactions: vec![],
+        }
+    }
+    /// Add a species to the network and return its index.
+    pub fn add_species(&mut self, species: Species) -> usize {
+        self.species.push(species);
+        self.species.len() - 1
+    }
+    /// Add a reaction to the network.
+    ///
+    /// Fails if the reaction refers to a species that has not been added.
+    pub fn add_reaction(&mut self, reaction: Reaction) -> Result<(), ModelError> {
+        let n = self.species.len();
+        if let Some(&i) = reaction
+            .initial
+            .iter()
+            .chain(reaction.fin.iter())
+            .find(|&&i| i >= n)
+        {
+            return Err(ModelError::InvalidParameter {
+                name: "species index",
+                value: i as f64,
+                expected: "the index of a species in the network",
+            });
+        }
+        self.reactions.push(reaction);
+        Ok(())
+    }
+    pub fn species(&self) -> &[Species] {
+        &self.species
+    }
+    /// Compute the logarithm of the equilibrium abundances at temperature `temp`.
+    pub fn log_yeq(&self, temp: f64) -> Array1<f64> {
+        let s = self.cosmology.entropy_density(temp);
+        self.species
+            .iter()
+            .map(|sp| (equilibrium_number_density(temp, sp.mass, sp.g, sp.statistics) / s).ln())
+            .collect()
+    }
+    /// Compute, for each reaction, the prefactor multiplying
+    /// `prod Y_initial - K prod Y_final` in `dY/dlogx` together with the
+    /// logarithm of the detailed-balance factor `K`.
+    fn reaction_coefficients(&self, temp: f64, weq: &Array1<f64>) -> Vec<(f64, f64)> {
+        let s: f64 = self.cosmology.entropy_density(temp);
+        let pf: f64 = -s / self.cosmology.dlogx_dt(temp);
+        self.reactions
+            .iter()
+            .map(|r| {
+                let rate = match &r.rate {
+                    Rate::Scattering(sigmav) => sigmav(temp),
+                    Rate::Decay(width) => {
+                        let z = self.species[r.initial[0]].mass / temp;
+                        width * z.cyl_bessel_k1_scaled() / z.cyl_bessel_kn_scaled(2)
+                    }
+                };
+                let nin = r.initial.len() as i32;
+                let logk = r.initial.iter().map(|&i| weq[i]).sum::<f64>()
+                    - r.fin.iter().map(|&i| weq[i]).sum::<f64>();
+                (-pf * r.symmetry * rate * s.powi(nin - 2), logk)
+            })
+            .collect()
+    }
+    fn dudt(&self, mut dw: ArrayViewMut1<f64>, w: ArrayView1<f64>, logx: f64) {
+        let temp: f64 = self.mref / logx.exp();
+        let weq = self.log_yeq(temp);
+        let ns = self.species.len();
+        dw.fill(0.0);
+        for (r, (c, logk)) in self
+            .reactions
+            .iter()
+            .zip(self.reaction_coefficients(temp, &weq))
+        {
+            let (a, b) = r.multiplicities(ns);
+            let fwd: f64 = a.iter().zip(w.iter()).map(|(ai, wi)| ai * wi).sum();
+            let bwd: f64 = logk + b.iter().zip(w.iter()).map(|(bi, wi)| bi * wi).sum::<f64>();
+            for i in 0..ns {
+                let nu = b[i] - a[i];
+                if nu != 0.0 {
+                    dw[i] += nu * c * ((fwd - w[i]).exp() - (bwd - w[i]).exp());
+                }
+            }
+        }
+    }
+    fn dfdu(&self, mut dw: ArrayViewMut2<f64>, w: ArrayView1<f64>, logx: f64) {
+        let temp: f64 = self.mref / logx.exp();
+        let weq = self.log_yeq(temp);
+        let ns = self.species.len();
+        dw.fill(0.0);
+        for (r, (c, logk)) in self
+            .reactions
+            .iter()
+            .zip(self.reaction_coefficients(temp, &weq))
+        {
+            let (a, b) = r.multiplicities(ns);
+            let fwd: f64 = a.iter().zip(w.iter()).map(|(ai, wi)| ai * wi).sum();
+            let bwd: f64 = logk + b.iter().zip(w.iter()).map(|(bi, wi)| bi * wi).sum::<f64>();
+            for i in 0..ns {
+                let nu = b[i] - a[i];
+                if nu == 0.0 {
+                    continue;
+                }
+                let ef = (fwd - w[i]).exp();
+                let eb = (bwd - w[i]).exp();
+                for l in 0..ns {
+                    let delta = if i == l { 1.0 } else { 0.0 };
+                    dw[[i, l]] += nu * c * ((a[l] - delta) * ef - (b[l] - delta) * eb);
+                }
+            }
+        }
+    }
+    /// Solve the network from `x0` to `x1` starting from the supplied
+    /// abundances `yinit`. The solution contains `log(Y_i)` as a function of
+    /// `log(x)`.
+    ///
+    /// Fails if `yinit` does not have one finite, positive abundance per
+    /// species, since the network is solved for their logarithms.
+    pub fn solve_from(
+        &self,
+        x0: f64,
+        x1: f64,
+        yinit: Array1<f64>,
+    ) -> Result<OdeSolution, ModelError> {
+        if yinit.len() != self.species.len() {
+            return Err(ModelError::InvalidParameter {
+                name: "yinit length",
+                value: yinit.len() as f64,
+                expected: "one initial abundance per species",
+            });
+        }
+        for &y in yinit.iter() {
+            check_parameter("yinit", y, "finite and > 0", |y| y.is_finite() && y > 0.0)?;
+        }
+        let uinit = yinit.mapv(f64::ln);
+        let tspan = (x0.ln(), x1.ln());
+
+        let dudt =
+            |mut dw: ArrayViewMut1<f64>, w: ArrayView1<f64>, logx: f64, p: &ReactionNetwork| {
+                p.dudt(dw.view_mut(), w.view(), logx)
+            };
+        let dfdu =
+            |mut dw: ArrayViewMut2<f64>, w: ArrayView1<f64>, logx: f64, p: &ReactionNetwork| {
+                p.dfdu(dw.view_mut(), w.view(), logx)
+            };
+
+        let mut integrator =
+            OdeIntegratorBuilder::default(&dudt, uinit, tspan, Radau5, self.clone())
+                .dfdu(&dfdu)
+                .reltol(self.ode_reltol)
+                .abstol(self.ode_abstol)
+                .build();
+
+        integrator.integrate();
+        Ok(integrator.sol)
+    }
+    /// Solve the network from `x0` to `x1` with all species starting in
+    /// equilibrium.
+    pub fn solve(&self, x0: f64, x1: f64) -> Result<OdeSolution, ModelError> {
+        let yinit = self.log_yeq(self.mref / x0).mapv(f64::exp);
+        self.solve_from(x0, x1, yinit)
+    }
+    /// Compute the relic density of each species from the solution at `x1`.
+    ///
+    /// Fails if the integration stops early or an abundance becomes
+    /// non-finite.
+    pub fn relic_densities(&self, x0: f64, x1: f64) -> Result<Vec<f64>, ModelError> {
+        let sol = self.solve(x0, x1)?;
+        check_solution(&sol, x1.ln(), x1, |logx, _| logx.exp())?;
+        let u = &sol.us[sol.us.len() - 1];
+        Ok(self
+            .species
+            .iter()
+            .zip(u.iter())
+            .map(|(sp, w)| w.exp() * sp.mass * S_TODAY / RHO_CRIT)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::kinetic_mixing::KineticMixing;
+
+    #[test]
+    fn test_network_matches_kinetic_mixing() {
+        let km = KineticMixing::new(1e3, 1e4, 1.0, 1e-1).unwrap();
+        let mx = km.mx();
+        let mut network = ReactionNetwork::new(mx);
+        network.ode_reltol = km.ode_reltol();
+        network.ode_abstol = km.ode_abstol();
+        let chi = network.add_species(Species::new("chi", mx, 2.0, Statistics::MaxwellBoltzmann));
+        let chibar = network.add_species(Species::new(
+            "chibar",
+            mx,
+            2.0,
+            Statistics::MaxwellBoltzmann,
+        ));
+        let sigmav = km.clone();
+        network
+            .add_reaction(Reaction::annihilation(chi, chibar, move |t| {
+                sigmav.thermal_cross_section(mx / t)
+            }))
+            .unwrap();
+        let rd = network.relic_densities(1.0, 1000.0).unwrap();
+        // `relic_density` tracks a single species, so chi and chibar each
+        // match it and their sum is twice it.
+        let omega = km.relic_density().unwrap().omega;
+        for &i in [chi, chibar].iter() {
+            assert!((rd[i] / omega - 1.0).abs() < 1e-3);
+        }
+        assert!(((rd[chi] + rd[chibar]) / (2.0 * omega) - 1.0).abs() < 1e-3);
+
+        assert!(network
+            .add_reaction(Reaction::decay(2, vec![chi], 1.0))
+            .is_err());
+        assert!(network.solve_from(1.0, 10.0, array![1e-3]).is_err());
+        for &y in [0.0, -1e-3, f64::NAN, f64::INFINITY].iter() {
+            assert!(network.solve_from(1.0, 10.0, array![1e-3, y]).is_err());
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! Standard Model input parameters.
+//!
+//! Models take their Standard Model inputs from an `SmParameters` instead of
+//! the global constants of `haliax_constants`. This allows the inputs to be
+//! varied within their uncertainties and the conventions of other codes to be
+//! matched. The defaults reproduce the values of `haliax_constants`.
+
+use crate::error::{check_parameter, ModelError};
+use haliax_constants::prelude::*;
+
+mod running;
+
+/// Value of the electromagnetic coupling used in the couplings of `V`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaScheme {
+    /// Thomson limit, `alpha(0) = 1 / 137.036`.
+    Thomson,
+    /// MS-bar coupling at the Z pole, `alpha(mZ) = 1 / 127.951`.
+    ZPole,
+}
+
+impl AlphaScheme {
+    pub fn alpha_em(self) -> f64 {
+        match self {
+            AlphaScheme::Thomson => 1.0 / 137.035_999_084,
+            AlphaScheme::ZPole => 1.0 / 127.951,
+        }
+    }
+    /// Scale at which `alpha_em` is defined.
+    pub fn scale(self) -> f64 {
+        match self {
+            AlphaScheme::Thomson => 0.0,
+            AlphaScheme::ZPole => Z_BOSON_MASS,
+        }
+    }
+}
+
+/// Definition of the heavy-quark masses. Light quark masses are MS-bar
+/// masses at 2 GeV in both schemes, since their pole masses are not
+/// meaningful.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QuarkMassScheme {
+    /// Pole masses of the charm, bottom and top quarks.
+    Pole,
+    /// MS-bar masses `m(m)` of the charm, bottom and top quarks.
+    MsBar,
+    /// MS-bar masses `m(m)` of the charm and bottom quarks and the pole mass
+    /// of the top quark, as in `haliax_constants` and the PDG summary tables.
+    Mixed,
+}
+
+impl QuarkMassScheme {
+    /// Masses of the up, down, strange, charm, bottom and top quarks.
+    ///
+    /// The light quark masses and the masses of `Mixed` are those of
+    /// `haliax_constants`. The other heavy-quark masses are the pole masses
+    /// of the charm and bottom quarks and the MS-bar mass of the top quark
+    /// from the PDG (2022) quark summary tables.
+    pub fn quark_masses(self) -> [f64; 6] {
+        let (mu, md, ms) = (UP_QUARK_MASS, DOWN_QUARK_MASS, STRANGE_QUARK_MASS);
+        match self {
+            QuarkMassScheme::Pole => [mu, md, ms, 1.67, 4.78, TOP_QUARK_MASS],
+            QuarkMassScheme::MsBar => [mu, md, ms, CHARM_QUARK_MASS, BOTTOM_QUARK_MASS, 162.5],
+            QuarkMassScheme::Mixed => [
+                mu,
+                md,
+                ms,
+                CHARM_QUARK_MASS,
+                BOTTOM_QUARK_MASS,
+                TOP_QUARK_MASS,
+            ],
+        }
+    }
+    /// Whether the input mass of `quark` is its pole mass.
+    pub fn is_pole_mass(self, quark: Quark) -> bool {
+        match (self, quark) {
+            (_, Quark::Up) | (_, Quark::Down) | (_, Quark::Strange) => false,
+            (QuarkMassScheme::Pole, _) => true,
+            (QuarkMassScheme::MsBar, _) => false,
+            (QuarkMassScheme::Mixed, quark) => quark == Quark::Top,
+        }
+    }
+}
+
+/// Quark flavours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quark {
+    Up,
+    Down,
+    Strange,
+    Charm,
+    Bottom,
+    Top,
+}
+
+impl Quark {
+    /// Whether the mass of the quark is an MS-bar mass at 2 GeV.
+    pub fn is_light(self) -> bool {
+        match self {
+            Quark::Up | Quark::Down | Quark::Strange => true,
+            Quark::Charm | Quark::Bottom | Quark::Top => false,
+        }
+    }
+}
+
+/// Standard Model inputs. All fields may be overridden individually; the
+/// cosine of the weak mixing angle follows from `sin_theta_weak_sqrd`.
+///
+/// If `running` is set, the electromagnetic coupling, the quark masses and
+/// the QCD corrections to quark pairs are evaluated at the scale of the
+/// process. Otherwise they are fixed at their input values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SmParameters {
+    pub alpha_em: f64,
+    /// Scale at which `alpha_em` is defined, zero for the Thomson limit.
+    pub alpha_em_scale: f64,
+    /// Strong coupling at the Z pole.
+    pub alpha_s_mz: f64,
+    /// Definition of the heavy-quark masses.
+    pub quark_mass_scheme: QuarkMassScheme,
+    /// Evaluate couplings and quark masses at the scale of the process.
+    pub running: bool,
+    pub sin_theta_weak_sqrd: f64,
+    pub z_boson_mass: f64,
+    pub w_boson_mass: f64,
+    pub higgs_mass: f64,
+    pub higgs_vev: f64,
+    pub up_quark_mass: f64,
+    pub down_quark_mass: f64,
+    pub strange_quark_mass: f64,
+    pub charm_quark_mass: f64,
+    pub bottom_quark_mass: f64,
+    pub top_quark_mass: f64,
+    pub electron_mass: f64,
+    pub muon_mass: f64,
+    pub tau_mass: f64,
+}
+
+impl Default for SmParameters {
+    fn default() -> Self {
+        SmParameters {
+            alpha_em: ALPHA_EM,
+            alpha_em_scale: 0.0,
+            alpha_s_mz: 0.1179,
+            quark_mass_scheme: QuarkMassScheme::Mixed,
+            running: false,
+            sin_theta_weak_sqrd: SIN_THETA_WEAK_SQRD,
+            z_boson_mass: Z_BOSON_MASS,
+            w_boson_mass: W_BOSON_MASS,
+            higgs_mass: HIGGS_MASS,
+            higgs_vev: HIGGS_VEV,
+            up_quark_mass: UP_QUARK_MASS,
+            down_quark_mass: DOWN_QUARK_MASS,
+            strange_quark_mass: STRANGE_QUARK_MASS,
+            charm_quark_mass: CHARM_QUARK_MASS,
+            bottom_quark_mass: BOTTOM_QUARK_MASS,
+            top_quark_mass: TOP_QUARK_MASS,
+            electron_mass: ELECTRON_MASS,
+            muon_mass: MUON_MASS,
+            tau_mass: TAU_MASS,
+        }
+    }
+}
+
+impl SmParameters {
+    /// Replace the electromagnetic coupling with its value in `scheme`.
+    pub fn with_alpha_scheme(mut self, scheme: AlphaScheme) -> Self {
+        self.alpha_em = scheme.alpha_em();
+        self.alpha_em_scale = scheme.scale();
+        self
+    }
+    /// Replace the quark masses with their values in `scheme`.
+    pub fn with_quark_mass_scheme(mut self, scheme: QuarkMassScheme) -> Self {
+        let [mu, md, ms, mc, mb, mt] = scheme.quark_masses();
+        self.up_quark_mass = mu;
+        self.down_quark_mass = md;
+        self.strange_quark_mass = ms;
+        self.charm_quark_mass = mc;
+        self.bottom_quark_mass = mb;
+        self.top_quark_mass = mt;
+        self.quark_mass_scheme = scheme;
+        self
+    }
+    /// Evaluate couplings and quark masses at the scale of the process.
+    pub fn with_running(mut self, running: bool) -> Self {
+        self.running = running;
+        self
+    }
+    /// Input mass of `quark`.
+    pub fn quark_mass(&self, quark: Quark) -> f64 {
+        match quark {
+            Quark::Up => self.up_quark_mass,
+            Quark::Down => self.down_quark_mass,
+            Quark::Strange => self.strange_quark_mass,
+            Quark::Charm => self.charm_quark_mass,
+            Quark::Bottom => self.bottom_quark_mass,
+            Quark::Top => self.top_quark_mass,
+        }
+    }
+    pub fn sin_theta_weak(&self) -> f64 {
+        self.sin_theta_weak_sqrd.sqrt()
+    }
+    pub fn cos_theta_weak(&self) -> f64 {
+        (1.0 - self.sin_theta_weak_sqrd).sqrt()
+    }
+    /// Check that the couplings are in range and the masses are positive.
+    pub fn validate(&self) -> Result<(), ModelError> {
+        check_parameter("alpha_em", self.alpha_em, "alpha_em > 0", |a| a > 0.0)?;
+        check_parameter(
+            "alpha_em_scale",
+            self.alpha_em_scale,
+            "alpha_em_scale >= 0",
+            |q| q >= 0.0,
+        )?;
+        check_parameter("alpha_s_mz", self.alpha_s_mz, "alpha_s_mz > 0", |a| a > 0.0)?;
+        check_parameter(
+            "sin_theta_weak_sqrd",
+            self.sin_theta_weak_sqrd,
+            "0 < sin_theta_weak_sqrd < 1",
+            |s| s > 0.0 && s < 1.0,
+        )?;
+        let masses = [
+            ("z_boson_mass", self.z_boson_mass),
+            ("w_boson_mass", self.w_boson_mass),
+            ("higgs_mass", self.higgs_mass),
+            ("higgs_vev", self.higgs_vev),
+            ("up_quark_mass", self.up_quark_mass),
+            ("down_quark_mass", self.down_quark_mass),
+            ("strange_quark_mass", self.strange_quark_mass),
+            ("charm_quark_mass", self.charm_quark_mass),
+            ("bottom_quark_mass", self.bottom_quark_mass),
+            ("top_quark_mass", self.top_quark_mass),
+            ("electron_mass", self.electron_mass),
+            ("muon_mass", self.muon_mass),
+            ("tau_mass", self.tau_mass),
+        ];
+        for (name, m) in masses.iter() {
+            check_parameter(name, *m, "a positive mass", |m| m > 0.0)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_defaults_match_haliax_constants() {
+        // Results with the default inputs are those of the global constants.
+        let sm = SmParameters::default();
+        let constants = [
+            (sm.alpha_em, ALPHA_EM),
+            (sm.sin_theta_weak_sqrd, SIN_THETA_WEAK_SQRD),
+            (sm.z_boson_mass, Z_BOSON_MASS),
+            (sm.w_boson_mass, W_BOSON_MASS),
+            (sm.higgs_mass, HIGGS_MASS),
+            (sm.higgs_vev, HIGGS_VEV),
+            (sm.up_quark_mass, UP_QUARK_MASS),
+            (sm.down_quark_mass, DOWN_QUARK_MASS),
+            (sm.strange_quark_mass, STRANGE_QUARK_MASS),
+            (sm.charm_quark_mass, CHARM_QUARK_MASS),
+            (sm.bottom_quark_mass, BOTTOM_QUARK_MASS),
+            (sm.top_quark_mass, TOP_QUARK_MASS),
+            (sm.electron_mass, ELECTRON_MASS),
+            (sm.muon_mass, MUON_MASS),
+            (sm.tau_mass, TAU_MASS),
+        ];
+        for (value, constant) in constants.iter() {
+            assert_eq!(value.to_bits(), constant.to_bits());
+        }
+        assert!(!sm.running);
+        assert_eq!(sm.with_quark_mass_scheme(sm.quark_mass_scheme), sm);
+    }
+}
@@ -0,0 +1,160 @@
+//! One-loop running of the electromagnetic and strong couplings and of the
+//! MS-bar quark masses.
+
+use super::{Quark, SmParameters};
+use std::f64::consts::PI;
+
+/// Below this scale the strong coupling, and with it the quark masses, are
+/// frozen.
+const QCD_MIN_SCALE: f64 = 1.0;
+
+/// Scale at which the masses of the light quarks are defined.
+const LIGHT_QUARK_SCALE: f64 = 2.0;
+
+/// Effective mass of the light quarks in the hadronic vacuum polarization.
+/// With it the one-loop running from `alpha(0)` gives `1 / alpha(mZ)` within
+/// half a percent of the measured value.
+const HADRONIC_SCALE: f64 = 0.3;
+
+/// One-loop coefficient of the QCD beta function with `nf` active flavours.
+fn beta0(nf: u32) -> f64 {
+    11.0 - 2.0 * f64::from(nf) / 3.0
+}
+
+impl SmParameters {
+    /// Heavy-quark thresholds of the strong coupling.
+    fn heavy_quark_thresholds(&self) -> [f64; 3] {
+        [
+            self.charm_quark_mass,
+            self.bottom_quark_mass,
+            self.top_quark_mass,
+        ]
+    }
+    /// Number of active quark flavours at the scale `q`.
+    fn active_flavours(&self, q: f64) -> u32 {
+        3 + self
+            .heavy_quark_thresholds()
+            .iter()
+            .filter(|&&m| m < q)
+            .count() as u32
+    }
+    /// Electromagnetic coupling at the scale `q`. Without running this is
+    /// `alpha_em`.
+    pub fn alpha_em_at(&self, q: f64) -> f64 {
+        if !self.running {
+            return self.alpha_em;
+        }
+        let inv = 1.0 / self.alpha_em + self.vacuum_polarization(self.alpha_em_scale)
+            - self.vacuum_polarization(q);
+        1.0 / inv
+    }
+    /// Shift in `1 / alpha` between zero momentum and the scale `q` from the
+    /// charged fermions lighter than `q`.
+    fn vacuum_polarization(&self, q: f64) -> f64 {
+        let light = HADRONIC_SCALE;
+        let fermions = [
+            (self.electron_mass, 1.0),
+            (self.muon_mass, 1.0),
+            (self.tau_mass, 1.0),
+            (self.up_quark_mass.max(light), 4.0 / 3.0),
+            (self.down_quark_mass.max(light), 1.0 / 3.0),
+            (self.strange_quark_mass.max(light), 1.0 / 3.0),
+            (self.charm_quark_mass, 4.0 / 3.0),
+            (self.bottom_quark_mass, 1.0 / 3.0),
+            (self.top_quark_mass, 4.0 / 3.0),
+        ];
+        // N_c Q_f^2 is folded into the second entry.
+        fermions
+            .iter()
+            .filter(|(m, _)| *m < q)
+            .map(|(m, nq2)| nq2 * (q / m).ln())
+            .sum::<f64>()
+            * 2.0
+            / (3.0 * PI)
+    }
+    /// Strong coupling at the scale `q`, run at one loop from
+    /// `alpha_s(mZ)` with flavour thresholds at the heavy-quark masses.
+    pub fn alpha_s_at(&self, q: f64) -> f64 {
+        let q = q.max(QCD_MIN_SCALE);
+        let mz = self.z_boson_mass;
+        let mut thresholds = self.heavy_quark_thresholds();
+        thresholds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        if q < mz {
+            thresholds.reverse();
+        }
+        let (lo, hi) = (q.min(mz), q.max(mz));
+
+        let mut mu = mz;
+        let mut inv = 1.0 / self.alpha_s_mz;
+        for &p in thresholds
+            .iter()
+            .filter(|&&m| m > lo && m < hi)
+            .chain(std::iter::once(&q))
+        {
+            let nf = self.active_flavours((mu * p).sqrt());
+            inv += beta0(nf) / (4.0 * PI) * (p * p / (mu * mu)).ln();
+            mu = p;
+        }
+        1.0 / inv
+    }
+    /// Ratio `m(q) / m(mu)` of MS-bar quark masses at one loop.
+    fn mass_running(&self, mu: f64, q: f64) -> f64 {
+        let (mu, q) = (mu.max(QCD_MIN_SCALE), q.max(QCD_MIN_SCALE));
+        let (lo, hi) = (mu.min(q), mu.max(q));
+        let mut points = [lo, lo, lo, hi];
+        for (p, &m) in points.iter_mut().zip(self.heavy_quark_thresholds().iter()) {
+            if m > lo && m < hi {
+                *p = m;
+            }
+        }
+        points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let mut ratio = 1.0;
+        let mut start = lo;
+        for &p in points.iter().filter(|&&p| p > lo) {
+            let nf = self.active_flavours((start * p).sqrt());
+            ratio *= (self.alpha_s_at(p) / self.alpha_s_at(start)).powf(4.0 / beta0(nf));
+            start = p;
+        }
+        if q >= mu {
+            ratio
+        } else {
+            1.0 / ratio
+        }
+    }
+    /// MS-bar mass of `quark` at the scale `q`.
+    ///
+    /// Light quark masses are taken to be defined at 2 GeV. Heavy quark masses
+    /// are `m(m)` if they are MS-bar masses in `quark_mass_scheme` and are
+    /// converted from pole masses at one loop otherwise. Without running this
+    /// is the input mass.
+    pub fn quark_mass_at(&self, quark: Quark, q: f64) -> f64 {
+        let mass = self.quark_mass(quark);
+        if !self.running {
+            return mass;
+        }
+        if quark.is_light() {
+            return mass * self.mass_running(LIGHT_QUARK_SCALE, q);
+        }
+        let msbar = if self.quark_mass_scheme.is_pole_mass(quark) {
+            mass / (1.0 + 4.0 * self.alpha_s_at(mass) / (3.0 * PI))
+        } else {
+            mass
+        };
+        msbar * self.mass_running(msbar, q)
+    }
+    /// QCD correction to the production of a quark pair through a vector
+    /// current at the scale `q`, in the massless limit,
+    /// `1 + a + r2 a^2 + r3 a^3` with `a = alpha_s / pi`. Without running
+    /// this is one.
+    pub fn qcd_correction(&self, q: f64) -> f64 {
+        if !self.running {
+            return 1.0;
+        }
+        let a = self.alpha_s_at(q) / PI;
+        let nf = f64::from(self.active_flavours(q));
+        let r2 = 1.9857 - 0.1152 * nf;
+        let r3 = -6.63694 - 1.20013 * nf - 0.00518 * nf * nf;
+        1.0 + a + r2 * a * a + r3 * a.powi(3)
+    }
+}
@@ -0,0 +1,130 @@
+use cyphus_integration::prelude::*;
+use cyphus_specfun::bessel::CylBesselK;
+use std::f64::consts::PI;
+
+/// Riemann zeta function evaluated at 3.
+const ZETA_3: f64 = 1.202_056_903_159_594_2;
+
+/// Statistics obeyed by a particle species.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Statistics {
+    MaxwellBoltzmann,
+    FermiDirac,
+    BoseEinstein,
+}
+
+impl Statistics {
+    /// Sign entering the distribution function `1 / (exp(E/T) + eta)`.
+    pub fn eta(self) -> f64 {
+        match self {
+            Statistics::MaxwellBoltzmann => 0.0,
+            Statistics::FermiDirac => 1.0,
+            Statistics::BoseEinstein => -1.0,
+        }
+    }
+    /// Phase-space occupation number at energy `e` and temperature `temp`
+    /// with vanishing chemical potential.
+    pub fn occupation(self, e: f64, temp: f64) -> f64 {
+        let eta = self.eta();
+        if eta == 0.0 {
+            (-e / temp).exp()
+        } else {
+            1.0 / ((e / temp).exp() + eta)
+        }
+    }
+}
+
+/// Compute the equilibrium number density of a species with mass `mass`,
+/// `g` internal degrees of freedom and statistics `stat` at temperature
+/// `temp` and vanishing chemical potential.
+pub fn equilibrium_number_density(temp: f64, mass: f64, g: f64, stat: Statistics) -> f64 {
+    let z = mass / temp;
+    if z == 0.0 {
+        let pf = match stat {
+            Statistics::MaxwellBoltzmann => 1.0,
+            Statistics::FermiDirac => 0.75 * ZETA_3,
+            Statistics::BoseEinstein => ZETA_3,
+        };
+        return pf * g * temp.powi(3) / (PI * PI);
+    }
+    match stat {
+        Statistics::MaxwellBoltzmann => {
+            g * mass * mass * temp * z.cyl_bessel_kn_scaled(2) * (-z).exp() / (2.0 * PI * PI)
+        }
+        _ if z > 1.0 => {
+            // Sum over Boltzmann-like terms: n = g m^2 T / (2pi^2) sum (-eta)^(k+1) K2(kz) / k
+            let eta = stat.eta();
+            let mut sum = 0.0;
+            for k in 1..50 {
+                let kf = k as f64;
+                let term =
+                    (-eta).powi(k + 1) * (kf * z).cyl_bessel_kn_scaled(2) * (-kf * z).exp() / kf;
+                sum += term;
+                if term.abs() < 1e-14 * sum.abs() {
+                    break;
+                }
+            }
+            g * mass * mass * temp * sum / (2.0 * PI * PI)
+        }
+        _ => {
+            // The Bessel series converges slowly for relativistic species, so
+            // integrate over momentum directly with y = p / T.
+            let gk = GaussKronrodIntegratorBuilder::default()
+                .epsabs(0.0)
+                .epsrel(1e-10)
+                .limit(100)
+                .build();
+            let integrand =
+                |y: f64| -> f64 { y * y * stat.occupation((y * y + z * z).sqrt(), 1.0) };
+            g * temp.powi(3) * gk.integrate(integrand, 0.0, f64::INFINITY).val / (2.0 * PI * PI)
+        }
+    }
+}
+
+/// Compute `int_0^inf dy y^2 f(y) occupation(sqrt(y^2 + z^2))` for the weight
+/// `f` of a thermodynamic quantity, in units of the temperature.
+fn momentum_integral<F>(z: f64, stat: Statistics, f: F) -> f64
+where
+    F: Fn(f64, f64) -> f64,
+{
+    let gk = GaussKronrodIntegratorBuilder::default()
+        .epsabs(0.0)
+        .epsrel(1e-8)
+        .limit(100)
+        .build();
+    let integrand = |y: f64| -> f64 {
+        let e = (y * y + z * z).sqrt();
+        y * y * f(y, e) * stat.occupation(e, 1.0)
+    };
+    gk.integrate(integrand, 0.0, f64::INFINITY).val
+}
+
+/// Compute the equilibrium energy density of a species with mass `mass`, `g`
+/// internal degrees of freedom and statistics `stat` at temperature `temp`
+/// and vanishing chemical potential.
+pub fn equilibrium_energy_density(temp: f64, mass: f64, g: f64, stat: Statistics) -> f64 {
+    let z = mass / temp;
+    g * temp.powi(4) * momentum_integral(z, stat, |_, e| e) / (2.0 * PI * PI)
+}
+
+/// Compute the heat capacity `d rho / dT` of a species with mass `mass`, `g`
+/// internal degrees of freedom and statistics `stat` in equilibrium at
+/// temperature `temp` and vanishing chemical potential. Since
+/// `d f / dT = E / T^2 f (1 - eta f)`, this is
+/// `g T^3 / (2 pi^2) int dy y^2 e^2 f (1 - eta f)`.
+pub fn equilibrium_heat_capacity(temp: f64, mass: f64, g: f64, stat: Statistics) -> f64 {
+    let z = mass / temp;
+    let eta = stat.eta();
+    let integral = momentum_integral(z, stat, |_, e| {
+        e * e * (1.0 - eta * stat.occupation(e, 1.0))
+    });
+    g * temp.powi(3) * integral / (2.0 * PI * PI)
+}
+
+/// Compute the equilibrium pressure of a species with mass `mass`, `g`
+/// internal degrees of freedom and statistics `stat` at temperature `temp`
+/// and vanishing chemical potential.
+pub fn equilibrium_pressure(temp: f64, mass: f64, g: f64, stat: Statistics) -> f64 {
+    let z = mass / temp;
+    g * temp.powi(4) * momentum_integral(z, stat, |y, e| y * y / e) / (6.0 * PI * PI)
+}
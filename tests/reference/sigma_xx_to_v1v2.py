@@ -0,0 +1,164 @@
+"""Reference values of sigma(chi chibar -> V1 V2) for
+test_sigma_xx_to_v1v2_reference and test_cs_electroweak.
+
+Independent of the closed form in the crate: the spin-summed squared matrix
+element of the t- and u-channel chi exchange diagrams,
+
+    M = g1 g2 vbar(p2) [g^nu S(p1 - k1) g^mu + g^mu S(p1 - k2) g^nu] u(p1)
+        eps1*_mu eps2*_nu,      S(q) = (qslash + mx) / (q^2 - mx^2),
+
+is evaluated numerically from explicit Dirac matrices and the massive
+polarisation sums -g_{mu rho} + k_mu k_rho / m^2, averaged over the initial
+spins and integrated over the scattering angle in the centre-of-mass frame,
+
+    sigma = int_{-1}^{1} dcos(theta) |M|^2 / (32 pi s) |k1| / |p1|,
+
+with 30-digit tanh-sinh quadrature.
+
+    python3 tests/reference/sigma_xx_to_v1v2.py
+
+Requires mpmath. Takes about a minute.
+"""
+from mpmath import mp, mpf, mpc, sqrt, pi, quad
+
+mp.dps = 30
+
+METRIC = [1, -1, -1, -1]
+
+
+def _gamma_matrices():
+    i = mpc(0, 1)
+    sigma = [
+        [[0, 1], [1, 0]],
+        [[0, -i], [i, 0]],
+        [[1, 0], [0, -1]],
+    ]
+    g0 = [[mpc(1 if (r == c and r < 2) else (-1 if r == c else 0)) for c in range(4)] for r in range(4)]
+    gammas = [g0]
+    for sg in sigma:
+        g = [[mpc(0)] * 4 for _ in range(4)]
+        for r in range(2):
+            for c in range(2):
+                g[r][c + 2] = mpc(sg[r][c])
+                g[r + 2][c] = -mpc(sg[r][c])
+        gammas.append(g)
+    return gammas
+
+
+GAMMA = _gamma_matrices()
+
+
+def matmul(a, b):
+    return [[sum(a[r][k] * b[k][c] for k in range(4)) for c in range(4)] for r in range(4)]
+
+
+def add(a, b):
+    return [[a[r][c] + b[r][c] for c in range(4)] for r in range(4)]
+
+
+def scale(x, a):
+    return [[x * a[r][c] for c in range(4)] for r in range(4)]
+
+
+def slash(p, m=0):
+    """pslash + m for the contravariant components p^mu."""
+    out = [[mpc(m if r == c else 0) for c in range(4)] for r in range(4)]
+    for mu in range(4):
+        out = add(out, scale(METRIC[mu] * p[mu], GAMMA[mu]))
+    return out
+
+
+def dot(p, q):
+    return sum(METRIC[mu] * p[mu] * q[mu] for mu in range(4))
+
+
+def polarisation_sum(k, m):
+    """-g_{mu rho} + k_mu k_rho / m^2 with lower indices."""
+    low = [METRIC[mu] * k[mu] for mu in range(4)]
+    return [
+        [(-METRIC[mu] if mu == rho else 0) + low[mu] * low[rho] / m**2 for rho in range(4)]
+        for mu in range(4)
+    ]
+
+
+def squared_matrix_element(mx, m1, m2, cme, cos_theta):
+    """Spin-averaged |M|^2 for g1 = g2 = 1."""
+    s = cme**2
+    e = cme / 2
+    p = sqrt(e**2 - mx**2)
+    e1 = (s + m1**2 - m2**2) / (2 * cme)
+    e2 = cme - e1
+    q = sqrt(e1**2 - m1**2)
+    sin_theta = sqrt(1 - cos_theta**2)
+    p1 = [e, 0, 0, p]
+    p2 = [e, 0, 0, -p]
+    k1 = [e1, q * sin_theta, 0, q * cos_theta]
+    k2 = [e2, -q * sin_theta, 0, -q * cos_theta]
+    qt = [p1[mu] - k1[mu] for mu in range(4)]
+    qu = [p1[mu] - k2[mu] for mu in range(4)]
+    st = scale(1 / (dot(qt, qt) - mx**2), slash(qt, mx))
+    su = scale(1 / (dot(qu, qu) - mx**2), slash(qu, mx))
+    left = slash(p2, -mx)
+    right = slash(p1, mx)
+    # O^{mu nu} and its Dirac conjugate Obar^{rho sigma}.
+    o = {}
+    obar = {}
+    for mu in range(4):
+        for nu in range(4):
+            o[mu, nu] = add(
+                matmul(matmul(GAMMA[nu], st), GAMMA[mu]),
+                matmul(matmul(GAMMA[mu], su), GAMMA[nu]),
+            )
+            obar[mu, nu] = add(
+                matmul(matmul(GAMMA[mu], st), GAMMA[nu]),
+                matmul(matmul(GAMMA[nu], su), GAMMA[mu]),
+            )
+    x = {key: matmul(matmul(left, val), right) for key, val in o.items()}
+    pol1 = polarisation_sum(k1, m1)
+    pol2 = polarisation_sum(k2, m2)
+    total = mpc(0)
+    for mu in range(4):
+        for nu in range(4):
+            for rho in range(4):
+                for sg in range(4):
+                    w = pol1[mu][rho] * pol2[nu][sg]
+                    if w == 0:
+                        continue
+                    a = x[mu, nu]
+                    b = obar[rho, sg]
+                    tr = sum(a[r][c] * b[c][r] for r in range(4) for c in range(4))
+                    total += w * tr
+    return total.real / 4
+
+
+def sigma_xx_to_v1v2(mx, m1, m2, cme, g1=1, g2=1):
+    """Cross section for distinct final-state bosons, without a symmetry factor."""
+    mx, m1, m2, cme = mpf(mx), mpf(m1), mpf(m2), mpf(cme)
+    s = cme**2
+    p = sqrt(s / 4 - mx**2)
+    e1 = (s + m1**2 - m2**2) / (2 * cme)
+    q = sqrt(e1**2 - m1**2)
+    integral = quad(lambda c: squared_matrix_element(mx, m1, m2, cme, c), [-1, 1])
+    return mpf(g1) ** 2 * mpf(g2) ** 2 * integral / (32 * pi * s) * q / p
+
+
+# (cme, m1, m2) for mx = 1000 and gvxx = 1. sigma_xx_to_v1v2 includes the
+# symmetry factor 1/2 of identical mediators.
+V1V2_POINTS = [
+    (2500.0, 100.0, 91.1876),
+    (3000.0, 900.0, 10.0),
+    (2000.5, 300.0, 50.0),
+    (2e5, 100.0, 1.0),
+]
+
+# sigma_xx_to_vz / theta_vz^2 for mx = 1000, mv = 500, gvxx = 1, cme = 3000
+# and the Z-boson mass of haliax-constants. chi couples to V with gvxx and to
+# Z with gvxx theta_vz, and V Z has no symmetry factor.
+Z_BOSON_MASS = 91.1876
+
+if __name__ == "__main__":
+    for cme, m1, m2 in V1V2_POINTS:
+        sigma = sigma_xx_to_v1v2(1000.0, m1, m2, cme) / 2
+        print("v1v2", cme, m1, m2, mp.nstr(sigma, 16))
+    sigma = sigma_xx_to_v1v2(1000.0, 500.0, Z_BOSON_MASS, 3000.0)
+    print("vz", mp.nstr(sigma, 16))
@@ -0,0 +1,67 @@
+"""Reference values of sigma(chi chibar -> V V) for test_sigma_xx_to_vv_reference.
+
+Evaluates the unsimplified closed form that the crate used before the
+cancellation-free rewrite, in 60-digit decimal arithmetic, so that the
+cancellations close to the thresholds and at large s are resolved exactly.
+
+    python3 tests/reference/sigma_xx_to_vv.py
+
+Requires mpmath.
+"""
+from mpmath import mp, mpf, sqrt, log, pi
+
+mp.dps = 60
+
+
+def sigma_xx_to_vv(mx, mv, gvxx, cme):
+    mx, mv, gvxx, cme = mpf(mx), mpf(mv), mpf(gvxx), mpf(cme)
+    ln2 = log(2)
+    mx2, mv2, s = mx**2, mv**2, cme**2
+    px = sqrt(s - 4 * mx2)
+    pv = sqrt(s - 4 * mv2)
+    r = s - 2 * mv2
+    t17 = -2 * mv2 + px * pv + s
+    a = (
+        -48 * px * pv * (4 * mx**4 + mx2 * s + 2 * mv**4) / (mv**4 + mx2 * (s - 4 * mv2))
+    )
+    b = (
+        48
+        * (
+            mx2 * (-2 * mv2 - 4 * mx2 + s) * ln2
+            + s * mv2 * 2 * ln2
+            + (2 * mx2 * (2 * mx2 + mv2) - s * (mx2 + 2 * mv2))
+            * log(
+                -(
+                    t17**2
+                    / (
+                        -(cme**4)
+                        + px * pv * s
+                        - 2 * mv**4
+                        + (-2 * px * pv + 4 * s) * mv2
+                        + 2 * mx2 * (s - 4 * mv2)
+                    )
+                )
+            )
+            + (-r) * (-2 * mv2 + 2 * mx2 + s) * log(-(t17 / (-s + px * pv + 2 * mv2)))
+        )
+        / (-r)
+    )
+    return gvxx**4 * (a + b) / (384 * pi * s * (s - 4 * mx2))
+
+
+# (mx, mv, gvxx, cme), given as the f64 values the test passes to the crate:
+# close to the thresholds sigma depends on the last bits of cme.
+POINTS = [
+    (1000.0, 100.0, 1.0, 3000.0),
+    (1000.0, 100.0, 1.0, 2000.0000002),
+    (100.0, 1000.0, 1.0, 2000.000001),
+    (1000.0, 999.9, 0.5, 2000.0000002),
+    (10000.0, 0.01, 1.0, 25000.0),
+    (1.0, 1e-06, 1.0, 1000.0),
+    (1000.0, 1.0, 0.1, 1000000.0),
+]
+
+if __name__ == "__main__":
+    for mx, mv, gvxx, cme in POINTS:
+        sigma = sigma_xx_to_vv(mx, mv, gvxx, cme)
+        print(mx, mv, gvxx, cme, mp.nstr(sigma, 16))